@@ -0,0 +1,25 @@
+#!/usr/bin/env bash
+# Build the program and the Borsh baseline it replaced for SBF, then benchmark
+# the per-turn instructions of both in Mollusk. Needs the Solana CLI for
+# `cargo build-sbf`. Results go to benches/compute_units.md.
+set -euo pipefail
+cd "$(dirname "$0")/.."
+
+out="$PWD/target/deploy"
+cargo build-sbf --sbf-out-dir "$out"
+
+# The baseline is the repository's first commit. It predates the rules crate
+# and the manifest, so it is built from this manifest without them.
+baseline="$(mktemp -d)"
+trap 'rm -rf "$baseline"' EXIT
+git show "$(git rev-list --max-parents=0 HEAD)":src/idl/lib.rs > "$baseline/lib.rs"
+sed -e '/^ludo-rules/d' \
+    -e 's/^name = "ludo-game"/name = "ludo-game-baseline"/' \
+    -e 's/^name = "ludo_game"/name = "ludo_game_baseline"/' \
+    -e '/^\[dev-dependencies\]/,/^$/d' \
+    Cargo.toml > "$baseline/Cargo.toml"
+cargo build-sbf --manifest-path "$baseline/Cargo.toml" --sbf-out-dir "$out"
+
+# Start from the baseline so every change is measured against it
+rm -f benches/compute_units.md
+SBF_OUT_DIR="$out" cargo test compute_units -- --ignored
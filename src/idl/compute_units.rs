@@ -0,0 +1,232 @@
+//! Compute-unit benchmarks for the instructions played every turn.
+//!
+//! They run the built program in Mollusk next to the Borsh program it replaced,
+//! so both have to be built for SBF first. `benches/compute_units.sh` builds them
+//! and runs the benchmark. The results are written to `benches/compute_units.md`:
+//! the Borsh baseline first, then the current program with the change from it.
+
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::{
+    prelude::Pubkey, AnchorSerialize, Discriminator, InstructionData, ToAccountMetas,
+};
+use bytemuck::Zeroable;
+use mollusk_svm::Mollusk;
+use mollusk_svm_bencher::MolluskComputeUnitBencher;
+use solana_account::Account;
+
+use crate::history::{GameHistory, HistoryEntry, HISTORY_INITIAL_CAPACITY};
+use crate::legacy::GameStateV1;
+use crate::{GameState, GameStatus};
+
+/// Slot the benchmarked roll request was made in
+const REQUEST_SLOT: u64 = 1_000;
+
+/// Account holding a zero-copy struct behind its discriminator, followed by
+/// `extra` zeroed bytes
+fn program_account<T: bytemuck::Pod + Discriminator>(value: &T, extra: usize) -> Account {
+    let mut data = T::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(bytemuck::bytes_of(value));
+    data.resize(data.len() + extra, 0);
+    Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: crate::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fn wallet() -> Account {
+    Account::new(1_000_000_000, 0, &anchor_lang::system_program::ID)
+}
+
+/// A two-player game in progress, the first player holding the turn with a
+/// roll requested and pieces spread over the board
+fn game_in_play(players: [Pubkey; 2], reveal: [u8; 32]) -> GameState {
+    let mut game = GameState::seated(&players, 100_000_000, GameStatus::Active);
+    game.history = 1;
+    game.commitments[0] = hash(&reveal).to_bytes();
+    game.commitments[1] = hash(&[1; 32]).to_bytes();
+    game.positions[0] = [10, 20, 0, 0];
+    game.positions[1] = [5, 30, 40, 0];
+    game.randomness_requested = 1;
+    game.randomness_request_slot = REQUEST_SLOT;
+    game
+}
+
+/// The same game in the Borsh layout of the baseline program, which rolled from
+/// the clock without a request
+fn baseline_game(players: [Pubkey; 2], dice_roll: Option<u8>) -> Account {
+    let game = GameStateV1 {
+        creator: players[0],
+        players: players.to_vec(),
+        deposited_players: players.to_vec(),
+        max_players: 2,
+        bet_amount: 100_000_000,
+        total_bet: 200_000_000,
+        platform_fee: 20_000_000,
+        prize_pool: 180_000_000,
+        track_length: 56,
+        positions: vec![vec![10, 20, 0, 0], vec![5, 30, 40, 0]],
+        home_counts: vec![0, 0],
+        start_offsets: vec![0, 26],
+        home_entry_positions: vec![50, 24],
+        safe_zones: vec![8, 13, 21, 26, 34, 39, 47],
+        game_state: GameStatus::Active as u8,
+        dice_roll,
+        missed_turns: vec![0, 0],
+        seed: "bench".to_string(),
+        ..Default::default()
+    };
+    let mut data = GameState::DISCRIMINATOR.to_vec();
+    game.serialize(&mut data).unwrap();
+    data.resize(8 + GameStateV1::SIZE, 0);
+    Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: crate::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Bench the baseline's roll and move, under the names of the instructions that
+/// replaced them
+fn bench_baseline(players: [Pubkey; 2], game_key: Pubkey) {
+    let mut mollusk = Mollusk::new(&crate::ID, "ludo_game_baseline");
+    mollusk.warp_to_slot(REQUEST_SLOT + 1);
+
+    let metas = vec![
+        AccountMeta::new(game_key, false),
+        AccountMeta::new(players[0], true),
+    ];
+    let request_randomness = Instruction::new_with_bytes(
+        crate::ID,
+        crate::instruction::RequestRandomness::DISCRIMINATOR,
+        metas.clone(),
+    );
+    let move_piece = Instruction::new_with_bytes(
+        crate::ID,
+        &crate::instruction::MovePiece { piece_index: 1 }.data(),
+        metas,
+    );
+
+    let waiting = [
+        (game_key, baseline_game(players, None)),
+        (players[0], wallet()),
+    ];
+    let rolled = [
+        (game_key, baseline_game(players, Some(4))),
+        (players[0], wallet()),
+    ];
+    MolluskComputeUnitBencher::new(mollusk)
+        .bench(("request_randomness", &request_randomness, &waiting))
+        .bench(("move_piece", &move_piece, &rolled))
+        .must_pass(true)
+        .execute();
+}
+
+#[test]
+#[ignore = "needs both programs built for SBF, run benches/compute_units.sh"]
+fn compute_units() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let reveal = [9; 32];
+    let game_key = Pubkey::new_unique();
+    let history_key =
+        Pubkey::find_program_address(&[b"history".as_ref(), game_key.as_ref()], &crate::ID).0;
+
+    bench_baseline(players, game_key);
+
+    let mut mollusk = Mollusk::new(&crate::ID, "ludo_game");
+    mollusk.warp_to_slot(REQUEST_SLOT + 1);
+    let (slot_hashes_key, slot_hashes) = mollusk.sysvars.keyed_account_for_slot_hashes_sysvar();
+    let system_program = mollusk_svm::program::keyed_account_for_system_program();
+    let program = (
+        crate::ID,
+        mollusk_svm::program::create_program_account_loader_v3(&crate::ID),
+    );
+
+    let requested = game_in_play(players, reveal);
+    let mut rolled = requested;
+    rolled.randomness_requested = 0;
+    rolled.dice_roll = 4;
+    let mut waiting = rolled;
+    waiting.dice_roll = 0;
+
+    let mut history = GameHistory::zeroed();
+    history.game = game_key;
+    let accounts = |game: &GameState| {
+        vec![
+            (game_key, program_account(game, 0)),
+            (players[0], wallet()),
+            (
+                history_key,
+                program_account(&history, HISTORY_INITIAL_CAPACITY * HistoryEntry::SIZE),
+            ),
+            (slot_hashes_key, slot_hashes.clone()),
+            system_program.clone(),
+            program.clone(),
+        ]
+    };
+
+    let randomness_metas = crate::accounts::SimpleRandomness {
+        game: game_key,
+        player: players[0],
+        session: None,
+        history: Some(history_key),
+        profile: None,
+        recent_slothashes: sysvar::slot_hashes::ID,
+        system_program: anchor_lang::system_program::ID,
+    }
+    .to_account_metas(None);
+    let request_randomness = Instruction::new_with_bytes(
+        crate::ID,
+        &crate::instruction::RequestRandomness { reveal }.data(),
+        randomness_metas.clone(),
+    );
+    let roll_and_move = Instruction::new_with_bytes(
+        crate::ID,
+        &crate::instruction::RollAndMove {
+            reveal,
+            priorities: vec![1, 0],
+        }
+        .data(),
+        randomness_metas,
+    );
+    let request_roll = Instruction::new_with_bytes(
+        crate::ID,
+        &crate::instruction::RequestRoll {}.data(),
+        crate::accounts::RequestRoll {
+            game: game_key,
+            player: players[0],
+            session: None,
+            history: Some(history_key),
+            system_program: anchor_lang::system_program::ID,
+        }
+        .to_account_metas(None),
+    );
+    let move_piece = Instruction::new_with_bytes(
+        crate::ID,
+        &crate::instruction::MovePiece { piece_index: 1 }.data(),
+        crate::accounts::MovePiece {
+            game: game_key,
+            player: players[0],
+            session: None,
+            history: Some(history_key),
+            profile: None,
+        }
+        .to_account_metas(None),
+    );
+
+    let (requested, rolled, waiting) =
+        (accounts(&requested), accounts(&rolled), accounts(&waiting));
+    MolluskComputeUnitBencher::new(mollusk)
+        .bench(("request_roll", &request_roll, &waiting))
+        .bench(("request_randomness", &request_randomness, &requested))
+        .bench(("move_piece", &move_piece, &rolled))
+        .bench(("roll_and_move", &roll_and_move, &requested))
+        .must_pass(true)
+        .execute();
+}
@@ -0,0 +1,444 @@
+//! Append-only log of every roll, move, skipped turn and elimination in a game.
+//!
+//! The log lives in its own PDA next to the game and grows as the game goes on,
+//! so a finished game can be replayed off-chain through the rules engine and
+//! checked against the final `GameState`, which on its own only keeps where the
+//! pieces ended up.
+
+use std::cell::RefMut;
+
+use anchor_lang::prelude::*;
+
+use crate::rules::{Board, MoveOutcome, BASE, MAX_SEATS, PIECES_PER_PLAYER};
+use crate::{GameState, TurnSkipReason};
+
+/// Entries a new log has room for
+pub const HISTORY_INITIAL_CAPACITY: usize = 256;
+/// Entries a log grows by once it is nearly full
+pub const HISTORY_GROWTH: usize = 128;
+/// Most entries one turn appends after its roll request: the roll, then the move
+/// or skip that follows it
+pub const HISTORY_TURN_ENTRIES: usize = 2;
+
+/// Offset of the first entry in a log account, after the discriminator and header
+const ENTRIES_OFFSET: usize = 8 + GameHistory::SIZE;
+
+/// What a history entry records
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum HistoryKind {
+    Roll,
+    Move,
+    Skip,
+    Eliminated,
+}
+
+impl HistoryKind {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(HistoryKind::Roll),
+            1 => Some(HistoryKind::Move),
+            2 => Some(HistoryKind::Skip),
+            3 => Some(HistoryKind::Eliminated),
+            _ => None,
+        }
+    }
+}
+
+/// One logged action. `value` is the die for rolls and moves and the
+/// `TurnSkipReason` for skipped turns; the piece fields are only set for moves.
+#[zero_copy]
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub timestamp: i64,
+    pub kind: u8, // HistoryKind
+    pub seat: u8,
+    pub value: u8,
+    pub piece: u8,
+    pub from: u8,
+    pub to: u8,
+    pub captures: u8, // Opponent pieces sent back to base
+    pub _padding: [u8; 1],
+}
+
+impl HistoryEntry {
+    pub const SIZE: usize = std::mem::size_of::<HistoryEntry>();
+
+    pub fn roll(seat: u8, value: u8, timestamp: i64) -> Self {
+        HistoryEntry {
+            timestamp,
+            kind: HistoryKind::Roll as u8,
+            seat,
+            value,
+            ..HistoryEntry::default()
+        }
+    }
+
+    pub fn moved(seat: u8, dice_value: u8, outcome: &MoveOutcome, timestamp: i64) -> Self {
+        HistoryEntry {
+            timestamp,
+            kind: HistoryKind::Move as u8,
+            seat,
+            value: dice_value,
+            piece: outcome.mv.piece,
+            from: outcome.mv.from,
+            to: outcome.mv.to,
+            captures: outcome.captures().len() as u8,
+            ..HistoryEntry::default()
+        }
+    }
+
+    pub fn skip(seat: u8, reason: TurnSkipReason, timestamp: i64) -> Self {
+        HistoryEntry {
+            timestamp,
+            kind: HistoryKind::Skip as u8,
+            seat,
+            value: reason as u8,
+            ..HistoryEntry::default()
+        }
+    }
+
+    pub fn eliminated(seat: u8, timestamp: i64) -> Self {
+        HistoryEntry {
+            timestamp,
+            kind: HistoryKind::Eliminated as u8,
+            seat,
+            ..HistoryEntry::default()
+        }
+    }
+}
+
+/// Why replaying a log did not reproduce the game
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The account holds fewer entries than its header counts
+    Truncated,
+    /// Entry at this index has an unknown kind
+    UnknownEntry(usize),
+    /// Move at this index was logged without a roll before it
+    MissingRoll(usize),
+    /// Roll at this index was logged while the previous roll was still to be played
+    UnplayedRoll(usize),
+    /// Entry at this index was logged for a seat that did not hold the turn
+    OutOfTurn(usize),
+    /// Move at this index is not legal at that point, or lands somewhere else
+    IllegalMove(usize),
+    /// The replayed pieces do not end where the game says they are
+    PositionsMismatch,
+}
+
+/// Move log of one game, seeded by `[b"history", game]`. The header is followed
+/// in the account by `count` entries, with room for more up to the account's end.
+#[account(zero_copy)]
+pub struct GameHistory {
+    pub game: Pubkey,
+    pub count: u64, // Entries appended so far
+}
+
+impl GameHistory {
+    pub const SIZE: usize = std::mem::size_of::<GameHistory>();
+
+    /// Account size, discriminator included, of a log with room for `capacity` entries
+    pub const fn space(capacity: usize) -> usize {
+        ENTRIES_OFFSET + capacity * HistoryEntry::SIZE
+    }
+
+    /// Header of a log account's data
+    pub fn header(data: &[u8]) -> GameHistory {
+        bytemuck::pod_read_unaligned(&data[8..ENTRIES_OFFSET])
+    }
+
+    /// Entries a log account has room for
+    pub fn capacity(data: &[u8]) -> usize {
+        (data.len() - ENTRIES_OFFSET) / HistoryEntry::SIZE
+    }
+
+    /// Every entry in a log account's data, oldest first
+    #[allow(unknown_lints, clippy::chunks_exact_to_as_chunks)] // as_chunks needs Rust 1.88
+    pub fn entries(data: &[u8]) -> std::result::Result<Vec<HistoryEntry>, ReplayError> {
+        let count = Self::header(data).count as usize;
+        if count > Self::capacity(data) {
+            return Err(ReplayError::Truncated);
+        }
+        Ok(data[ENTRIES_OFFSET..]
+            .chunks_exact(HistoryEntry::SIZE)
+            .take(count)
+            .map(bytemuck::pod_read_unaligned)
+            .collect())
+    }
+
+    /// Rebuild the game from an empty board by replaying a log account's entries
+    /// through the rules engine, following whose turn it is, and check the result
+    /// against the game's stored positions
+    pub fn replay(data: &[u8], game: &GameState) -> std::result::Result<Board, ReplayError> {
+        let mut state = *game;
+        state.players = game.deposited_players;
+        state.positions = [[BASE; PIECES_PER_PLAYER]; MAX_SEATS];
+        state.home_counts = [0; MAX_SEATS];
+        state.turn = 0;
+
+        let mut dice = None;
+        for (index, entry) in Self::entries(data)?.iter().enumerate() {
+            let kind = HistoryKind::from_u8(entry.kind)
+                .filter(|_| entry.seat < state.player_count)
+                .ok_or(ReplayError::UnknownEntry(index))?;
+            let seat_to_play = match kind {
+                HistoryKind::Move => state.moving_seat(),
+                HistoryKind::Roll | HistoryKind::Skip => state.turn,
+                HistoryKind::Eliminated => entry.seat,
+            };
+            if entry.seat != seat_to_play {
+                return Err(ReplayError::OutOfTurn(index));
+            }
+
+            match kind {
+                HistoryKind::Roll => {
+                    if dice.replace(entry.value).is_some() {
+                        return Err(ReplayError::UnplayedRoll(index));
+                    }
+                }
+                HistoryKind::Move => {
+                    let dice_value = dice.take().ok_or(ReplayError::MissingRoll(index))?;
+                    let mut board = state.board();
+                    let outcome = board
+                        .apply_move(entry.seat, entry.piece, dice_value)
+                        .map_err(|_| ReplayError::IllegalMove(index))?;
+                    if dice_value != entry.value
+                        || outcome.mv.from != entry.from
+                        || outcome.mv.to != entry.to
+                        || outcome.captures().len() != entry.captures as usize
+                    {
+                        return Err(ReplayError::IllegalMove(index));
+                    }
+                    state.positions = board.positions;
+                    state.home_counts[entry.seat as usize] = board.home_count(entry.seat);
+                    if !outcome.extra_turn {
+                        state.turn = next_turn(&state);
+                    }
+                }
+                HistoryKind::Skip => {
+                    dice = None;
+                    state.turn = next_turn(&state);
+                }
+                HistoryKind::Eliminated => {
+                    state.players[entry.seat as usize] = Pubkey::default();
+                    if entry.seat == state.turn {
+                        dice = None;
+                        state.turn = next_turn(&state);
+                    }
+                }
+            }
+        }
+
+        if state.positions != game.positions {
+            return Err(ReplayError::PositionsMismatch);
+        }
+        Ok(state.board())
+    }
+}
+
+/// Seat the turn passes to, as `next_active_player` picks it on-chain. Stays put
+/// once nobody else is left to play, which only happens as the game ends.
+fn next_turn(state: &GameState) -> u8 {
+    let seats = state.player_count;
+    (1..=seats)
+        .map(|step| (state.turn + step) % seats)
+        .find(|seat| {
+            state.players[*seat as usize] != Pubkey::default() && state.plays_on(*seat as usize)
+        })
+        .unwrap_or(state.turn)
+}
+
+/// A game's log opened for appending, over its account's data
+pub struct HistoryLog<'a> {
+    data: RefMut<'a, [u8]>,
+}
+
+impl<'a> HistoryLog<'a> {
+    pub fn new(data: RefMut<'a, [u8]>) -> Self {
+        HistoryLog { data }
+    }
+
+    /// Entries that can still be appended before the account has to grow
+    pub fn room(&self) -> usize {
+        GameHistory::capacity(&self.data)
+            .saturating_sub(GameHistory::header(&self.data).count as usize)
+    }
+
+    /// Append an entry. Callers make sure there is room first.
+    pub fn push(&mut self, entry: HistoryEntry) {
+        let mut header = GameHistory::header(&self.data);
+        let offset = ENTRIES_OFFSET + header.count as usize * HistoryEntry::SIZE;
+        self.data[offset..offset + HistoryEntry::SIZE].copy_from_slice(bytemuck::bytes_of(&entry));
+        header.count += 1;
+        self.data[8..ENTRIES_OFFSET].copy_from_slice(bytemuck::bytes_of(&header));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::{apply_dice_roll, play_piece, GameStatus};
+
+    /// A two-player game that has just started, keeping a log
+    fn new_game() -> GameState {
+        let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut game = GameState::seated(&players, 0, GameStatus::Active);
+        game.history = 1;
+        game
+    }
+
+    /// Data of an empty log account with room for `capacity` entries
+    fn new_log(capacity: usize) -> RefCell<Vec<u8>> {
+        let mut data = vec![0; GameHistory::space(capacity)];
+        data[..8].copy_from_slice(GameHistory::DISCRIMINATOR);
+        RefCell::new(data)
+    }
+
+    fn append(log: &RefCell<Vec<u8>>) -> HistoryLog<'_> {
+        HistoryLog::new(RefMut::map(log.borrow_mut(), |data| data.as_mut_slice()))
+    }
+
+    /// Play every roll through the on-chain helpers, moving the first piece that can
+    fn play(game: &mut GameState, log: &RefCell<Vec<u8>>, rolls: impl IntoIterator<Item = u8>) {
+        for roll in rolls {
+            if game.status() != GameStatus::Active {
+                break;
+            }
+            let mut history = append(log);
+            apply_dice_roll(game, Pubkey::default(), roll, 0, Some(&mut history));
+            if let Some(dice) = game.dice_roll() {
+                let legal = game.board().legal_moves(game.moving_seat(), dice);
+                let player = game.players[game.turn as usize];
+                let piece = legal.as_slice()[0].piece;
+                play_piece(
+                    game,
+                    Pubkey::default(),
+                    player,
+                    piece,
+                    0,
+                    Some(&mut history),
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    /// Dice from a fixed linear congruential sequence
+    fn dice() -> impl Iterator<Item = u8> {
+        let mut state = 7u32;
+        std::iter::repeat_with(move || {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (state >> 16) as u8 % 6 + 1
+        })
+    }
+
+    fn entry(kind: HistoryKind, seat: u8, value: u8) -> HistoryEntry {
+        HistoryEntry {
+            kind: kind as u8,
+            seat,
+            value,
+            ..HistoryEntry::default()
+        }
+    }
+
+    #[test]
+    fn replay_reproduces_a_whole_game() {
+        let mut game = new_game();
+        let log = new_log(8_192);
+        play(&mut game, &log, dice().take(5_000));
+        assert!(game.status() == GameStatus::Completed);
+
+        let data = log.borrow();
+        assert!(GameHistory::header(&data).count as usize > HISTORY_INITIAL_CAPACITY);
+        let board = GameHistory::replay(&data, &game).unwrap();
+        assert!(board.positions == game.positions);
+    }
+
+    #[test]
+    fn replay_rejects_a_move_by_a_seat_that_did_not_roll() {
+        let game = new_game();
+        let log = new_log(4);
+        let mut history = append(&log);
+        history.push(entry(HistoryKind::Roll, 0, 6));
+        history.push(HistoryEntry {
+            piece: 0,
+            from: BASE,
+            to: 1,
+            ..entry(HistoryKind::Move, 1, 6)
+        });
+        drop(history);
+
+        assert_eq!(
+            GameHistory::replay(&log.borrow(), &game).unwrap_err(),
+            ReplayError::OutOfTurn(1)
+        );
+    }
+
+    #[test]
+    fn replay_rejects_a_roll_out_of_turn() {
+        let game = new_game();
+        let log = new_log(4);
+        let mut history = append(&log);
+        history.push(entry(HistoryKind::Roll, 0, 3));
+        history.push(entry(
+            HistoryKind::Skip,
+            0,
+            TurnSkipReason::NoLegalMoves as u8,
+        ));
+        history.push(entry(HistoryKind::Roll, 0, 6));
+        drop(history);
+
+        assert_eq!(
+            GameHistory::replay(&log.borrow(), &game).unwrap_err(),
+            ReplayError::OutOfTurn(2)
+        );
+    }
+
+    #[test]
+    fn replay_rejects_a_second_roll_before_the_first_is_played() {
+        let game = new_game();
+        let log = new_log(4);
+        let mut history = append(&log);
+        history.push(entry(HistoryKind::Roll, 0, 6));
+        history.push(entry(HistoryKind::Roll, 0, 5));
+        drop(history);
+
+        assert_eq!(
+            GameHistory::replay(&log.borrow(), &game).unwrap_err(),
+            ReplayError::UnplayedRoll(1)
+        );
+    }
+
+    #[test]
+    fn replay_rejects_a_log_that_does_not_match_the_game() {
+        let mut game = new_game();
+        let log = new_log(512);
+        play(&mut game, &log, dice().take(100));
+        game.positions[1][3] += 1;
+
+        assert_eq!(
+            GameHistory::replay(&log.borrow(), &game).unwrap_err(),
+            ReplayError::PositionsMismatch
+        );
+    }
+
+    #[test]
+    fn replay_rejects_a_log_missing_entries() {
+        let game = new_game();
+        let log = new_log(2);
+        let mut history = append(&log);
+        history.push(entry(HistoryKind::Roll, 0, 3));
+        history.push(entry(HistoryKind::Skip, 0, 0));
+        assert_eq!(history.room(), 0);
+        drop(history);
+        log.borrow_mut().truncate(GameHistory::space(1));
+
+        assert_eq!(
+            GameHistory::replay(&log.borrow(), &game).unwrap_err(),
+            ReplayError::Truncated
+        );
+    }
+}
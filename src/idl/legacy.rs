@@ -0,0 +1,270 @@
+//! Game account layouts from before the current `GameState`, kept so
+//! `migrate_game` can read live accounts and rewrite them in the current layout.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+use bytemuck::Zeroable;
+
+use crate::rules::{self, BASE, CROSS_TRACK_SQUARES, MAX_SAFE_SQUARES, PIECES_PER_PLAYER};
+use crate::{ErrorCode, GameState, RuleSet, MAX_SEED_LEN};
+
+/// Read a game account written in the original Borsh layout, recognised by its
+/// size, and return it in the current layout.
+pub fn read_legacy_game(data: &[u8]) -> Result<GameState> {
+    let body = data.get(8..).unwrap_or_default();
+    match body.len() {
+        GameStateV1::SIZE => {
+            let legacy = GameStateV1::deserialize(&mut &body[..])
+                .map_err(|_| ErrorCode::UnsupportedGameVersion)?;
+            legacy.upgrade()
+        }
+        GameState::SIZE if body[0] == GameState::VERSION => err!(ErrorCode::GameAlreadyMigrated),
+        _ => err!(ErrorCode::UnsupportedGameVersion),
+    }
+}
+
+/// The original Borsh layout with growable fields, which every game created
+/// before the switch to zero-copy is stored in. It has no version byte and is
+/// told apart by its size, the `1099` bytes its space calculation reserved.
+#[derive(AnchorSerialize, AnchorDeserialize, Default)]
+pub struct GameStateV1 {
+    pub creator: Pubkey,
+    pub players: Vec<Pubkey>, // Default key once a player is removed
+    pub deposited_players: Vec<Pubkey>,
+    pub max_players: u8,
+    pub bet_amount: u64,
+    pub total_bet: u64,
+    pub platform_fee: u64,
+    pub prize_pool: u64,
+    pub track_length: u8,
+    pub positions: Vec<Vec<u8>>,
+    pub home_counts: Vec<u8>,
+    pub start_offsets: Vec<u8>,
+    pub home_entry_positions: Vec<u8>, // Dropped, derived from start_offsets since version 2
+    pub safe_zones: Vec<u8>,
+    pub turn: u8,
+    pub last_move_time: i64,
+    pub winner: Option<Pubkey>,
+    pub second_place: Option<Pubkey>,
+    pub game_state: u8, // GameStatus, a unit enum stored as its index
+    pub dice_roll: Option<u8>,
+    pub consecutive_sixes: u8,
+    pub missed_turns: Vec<u8>,
+    pub randomness_requested: bool,
+    pub seed: String,
+    pub bump: u8,
+}
+
+impl GameStateV1 {
+    pub const SIZE: usize = 1099;
+    /// Version reported for this layout when it is migrated
+    pub const VERSION: u8 = 1;
+    /// Settings that were hard-coded rather than stored: a 10% fee, a minute per
+    /// turn and ejection after three missed turns
+    const FEE_BPS: u16 = 1_000;
+    const TURN_TIMEOUT: i64 = 60;
+    const MAX_MISSED_TURNS: u8 = 3;
+
+    /// Rewrite the account in the current layout.
+    ///
+    /// These games were played on the cross board with positions counted from
+    /// the square after the start square, as before version 2, and without house
+    /// rule flags or dice commitments. Players register a hash chain through
+    /// `rotate_commitment` before their first commit-reveal roll.
+    pub fn upgrade(&self) -> Result<GameState> {
+        let seats = self.players.len();
+        require!(
+            seats <= 4
+                && self.deposited_players.len() <= 4
+                && self.positions.len() == seats
+                && self
+                    .positions
+                    .iter()
+                    .all(|pieces| pieces.len() == PIECES_PER_PLAYER)
+                && self.home_counts.len() == seats
+                && self.missed_turns.len() == seats
+                && self.start_offsets.len() <= 4
+                && self.safe_zones.len() <= MAX_SAFE_SQUARES
+                && self.seed.len() <= MAX_SEED_LEN,
+            ErrorCode::UnsupportedGameVersion
+        );
+
+        let mut game = GameState::zeroed();
+        game.version = GameState::VERSION;
+        game.player_count = seats as u8;
+        game.max_players = self.max_players;
+        game.track_squares = CROSS_TRACK_SQUARES;
+        game.track_length = rules::finish_position(CROSS_TRACK_SQUARES);
+        game.turn = self.turn;
+        game.game_state = self.game_state;
+        game.dice_roll = self.dice_roll.unwrap_or(0);
+        game.consecutive_sixes = self.consecutive_sixes;
+        game.bet_amount = self.bet_amount;
+        game.total_bet = self.total_bet;
+        game.platform_fee = self.platform_fee;
+        game.prize_pool = self.prize_pool;
+        game.last_move_time = self.last_move_time;
+        game.turn_timeout = Self::TURN_TIMEOUT;
+        game.creator = self.creator;
+        game.players[..seats].copy_from_slice(&self.players);
+        game.deposited_players[..self.deposited_players.len()]
+            .copy_from_slice(&self.deposited_players);
+        game.winner = self.winner.unwrap_or_default();
+        game.second_place = self.second_place.unwrap_or_default();
+        game.entropy = hashv(&[self.seed.as_bytes(), self.creator.as_ref()]).to_bytes();
+        game.seed[..self.seed.len()].copy_from_slice(self.seed.as_bytes());
+        game.seed_len = self.seed.len() as u8;
+        game.bump = self.bump;
+        game.fee_bps = Self::FEE_BPS;
+        game.max_missed_turns = Self::MAX_MISSED_TURNS;
+        game.rules = RuleSet {
+            blockades: false,
+            ..RuleSet::STANDARD
+        }
+        .to_flags();
+        for (seat, pieces) in self.positions.iter().enumerate() {
+            for (piece, position) in pieces.iter().enumerate() {
+                game.positions[seat][piece] = match *position {
+                    BASE => BASE,
+                    position => position + 1,
+                };
+            }
+        }
+        game.home_counts[..seats].copy_from_slice(&self.home_counts);
+        game.missed_turns[..seats].copy_from_slice(&self.missed_turns);
+        game.start_offsets[..self.start_offsets.len()].copy_from_slice(&self.start_offsets);
+        game.safe_zones[..self.safe_zones.len()].copy_from_slice(&self.safe_zones);
+        game.safe_zone_count = self.safe_zones.len() as u8;
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GameStatus;
+
+    /// Account data of a baseline four-player game in progress, as the Borsh
+    /// layout stored it: discriminator, serialized fields, then zeroed slack
+    fn v1_account(creator: Pubkey) -> Vec<u8> {
+        let players: Vec<Pubkey> = (1..=4)
+            .map(|seed| Pubkey::new_from_array([seed; 32]))
+            .collect();
+        let legacy = GameStateV1 {
+            creator,
+            players: vec![creator, players[1], Pubkey::default(), players[3]],
+            deposited_players: vec![creator, players[1], players[2], players[3]],
+            max_players: 4,
+            bet_amount: 1_000,
+            total_bet: 4_000,
+            platform_fee: 100,
+            prize_pool: 3_600,
+            track_length: 56,
+            positions: vec![
+                vec![0, 1, 50, 56],
+                vec![0; 4],
+                vec![13, 0, 0, 0],
+                vec![0; 4],
+            ],
+            home_counts: vec![1, 0, 0, 0],
+            start_offsets: vec![0, 13, 26, 39],
+            home_entry_positions: vec![50, 11, 24, 37],
+            safe_zones: vec![8, 13, 21, 26, 34, 39, 47],
+            turn: 1,
+            last_move_time: 1_700_000_000,
+            game_state: GameStatus::Active as u8,
+            dice_roll: Some(4),
+            consecutive_sixes: 1,
+            missed_turns: vec![0, 0, 3, 1],
+            seed: "table-7".to_string(),
+            bump: 254,
+            ..Default::default()
+        };
+
+        let mut data = GameState::DISCRIMINATOR.to_vec();
+        legacy.serialize(&mut data).unwrap();
+        data.resize(8 + GameStateV1::SIZE, 0);
+        data
+    }
+
+    #[test]
+    fn v1_accounts_are_recognised_by_size() {
+        // Whatever the creator's first byte, which sits where the version byte is
+        for first_byte in [0, 3, GameState::VERSION, u8::MAX] {
+            let mut creator = [7; 32];
+            creator[0] = first_byte;
+            let data = v1_account(Pubkey::new_from_array(creator));
+            assert!(!crate::is_current_data(&data));
+            let upgraded = read_legacy_game(&data).unwrap();
+            assert_eq!(upgraded.creator, Pubkey::new_from_array(creator));
+        }
+    }
+
+    #[test]
+    fn v1_upgrade_keeps_the_game_in_play() {
+        let creator = Pubkey::new_unique();
+        let game = read_legacy_game(&v1_account(creator)).unwrap();
+
+        assert_eq!(game.version, GameState::VERSION);
+        assert!(game.status() == GameStatus::Active);
+        assert_eq!(game.player_count, 4);
+        assert_eq!(game.players[0], creator);
+        assert_eq!(game.players[2], Pubkey::default());
+        assert_eq!(game.deposited_players[2], Pubkey::new_from_array([3; 32]));
+        assert_eq!(game.turn, 1);
+        assert_eq!(game.dice_roll(), Some(4));
+        assert_eq!(game.consecutive_sixes, 1);
+        assert_eq!(game.winner(), None);
+        assert_eq!(
+            (game.total_bet, game.platform_fee, game.prize_pool),
+            (4_000, 100, 3_600)
+        );
+        assert_eq!(game.fee_bps, 1_000);
+        assert_eq!(game.turn_timeout, 60);
+        assert_eq!(game.max_missed_turns, 3);
+        assert_eq!(&game.missed_turns[..4], &[0, 0, 3, 1]);
+        assert_eq!(&game.seed[..game.seed_len as usize], b"table-7");
+        assert_eq!(game.bump, 254);
+        assert_eq!(game.oracle(), None);
+        assert_eq!(game.mint(), None);
+        assert_eq!(game.history, 0);
+        assert!(!game.rules().blockades && game.rules().extra_turn_on_six);
+
+        // Same squares in the current coordinates: one further from base
+        assert_eq!(game.positions[0], [BASE, 2, 51, 57]);
+        assert_eq!(game.positions[2], [14, BASE, BASE, BASE]);
+        assert_eq!(game.track_length, 57);
+        assert_eq!(game.home_counts[0], 1);
+        let board = game.board();
+        assert_eq!(board.absolute_square(0, 2), Some(1));
+        assert_eq!(board.absolute_square(2, 14), Some(39));
+        assert_eq!(game.safe_zone_count, 7);
+    }
+
+    #[test]
+    fn current_and_unknown_layouts_are_not_migrated() {
+        let mut data = vec![0; 8 + GameState::SIZE];
+        data[GameState::VERSION_OFFSET] = GameState::VERSION;
+        assert!(crate::is_current_data(&data));
+        assert!(
+            read_legacy_game(&data).is_err_and(|err| err == ErrorCode::GameAlreadyMigrated.into())
+        );
+
+        data[GameState::VERSION_OFFSET] = 3;
+        assert!(!crate::is_current_data(&data));
+        assert!(read_legacy_game(&data)
+            .is_err_and(|err| err == ErrorCode::UnsupportedGameVersion.into()));
+
+        data.truncate(8 + GameState::SIZE - 8);
+        assert!(read_legacy_game(&data)
+            .is_err_and(|err| err == ErrorCode::UnsupportedGameVersion.into()));
+    }
+
+    #[test]
+    fn v1_accounts_with_impossible_contents_are_refused() {
+        let mut data = v1_account(Pubkey::new_unique());
+        // Claim a fifth player in the players vector length
+        data[8 + 32] = 5;
+        assert!(read_legacy_game(&data).is_err());
+    }
+}
@@ -1,68 +1,243 @@
 use anchor_lang::prelude::*;
+use std::cell::RefMut;
+
 use anchor_lang::solana_program::{
-    clock::Clock, 
-    program::invoke_signed,
-    program::invoke,  
-    system_instruction
+    clock::Clock,
+    hash::{hash, hashv},
+    program::invoke,
+    system_instruction,
+    sysvar,
+};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        self,
+        spl_token_2022::{
+            extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+            state::Mint as MintState,
+        },
+        Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+pub mod history;
+pub mod legacy;
+pub mod rating;
+#[cfg(test)]
+mod compute_units;
+#[cfg(test)]
+mod program_tests;
+pub use ludo_rules as rules;
+
+use history::{
+    GameHistory, HistoryEntry, HistoryLog, HISTORY_GROWTH, HISTORY_INITIAL_CAPACITY,
+    HISTORY_TURN_ENTRIES,
+};
+use rules::{
+    Board, BoardLayout, MoveError, MoveOutcome, MAX_SAFE_SQUARES, MAX_SEATS, PIECES_PER_PLAYER,
 };
 
 declare_id!("FP3cFkwHy5hzDTw3LRWYdDgiEBD4yxPqT49uaLDB56fD");
 
+/// Seat counts the board layout supports; the config may only allow a subset
+pub const SUPPORTED_PLAYER_COUNTS: [u8; 4] = [2, 3, 4, 6];
+/// Upper bound on the platform fee (25%)
+pub const MAX_FEE_BPS: u16 = 2_500;
+/// Second place always receives 25% of the pot in games that pay two places
+pub const SECOND_PLACE_BPS: u16 = 2_500;
+/// Longest game seed, the limit on a single PDA seed. New games are seeded by their
+/// little-endian `u64` id, older ones by a caller-chosen string.
+pub const MAX_SEED_LEN: usize = 32;
+/// Longest a session key can stay valid (24 hours)
+pub const MAX_SESSION_SECONDS: i64 = 24 * 60 * 60;
+/// Open tables a single lobby page lists at once
+pub const LOBBY_CAPACITY: usize = 64;
+/// Most VRF oracles the config can allow games to use
+pub const MAX_ORACLES: usize = 4;
+/// Slots a requested roll stays revealable for, kept inside the 512 slots the
+/// SlotHashes sysvar remembers
+pub const ROLL_REQUEST_WINDOW_SLOTS: u64 = 256;
+
 #[program]
 pub mod ludo_game {
     use super::*;
 
+    /// Create the singleton program config. Only the program's upgrade authority
+    /// may call this; it becomes the config admin.
+    pub fn initialize_config(ctx: Context<InitializeConfig>, params: ConfigParams) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.bump = ctx.bumps.config;
+        config.apply(params)
+    }
+
+    /// Update the fee, treasury, table limits and timeouts. Games already created
+    /// keep the fee and timeouts they were created with.
+    pub fn update_config(ctx: Context<UpdateConfig>, params: ConfigParams) -> Result<()> {
+        ctx.accounts.config.apply(params)
+    }
+
+    /// Hand the config admin role to another wallet
+    pub fn transfer_admin(ctx: Context<UpdateConfig>, new_admin: Pubkey) -> Result<()> {
+        require_keys_neq!(new_admin, Pubkey::default(), ErrorCode::InvalidAdmin);
+        ctx.accounts.config.admin = new_admin;
+        Ok(())
+    }
+
     /// Initialize a new Ludo game.
+    ///
+    /// The game takes the next id from the global `GameCounter` and its PDA is
+    /// derived from that id, so games can be enumerated by id range. The game is
+    /// listed in page `lobby_page` of the `Lobby` for its mint, bet and table size
+    /// while it waits for players. A full page fails with `LobbyFull`, and the client
+    /// retries on the next one.
+    /// `commitment` is the head of the creator's dice hash chain, see `request_randomness`.
+    /// Passing an `oracle` authority switches the game to VRF rolls through
+    /// `request_roll`/`fulfill_randomness` instead of commit-reveal. Only oracles
+    /// the admin listed in the config are accepted.
+    /// Passing a `mint` makes it a token table: bets are held in an associated
+    /// token vault owned by the game PDA instead of as lamports. Token-2022 mints
+    /// are limited to `ALLOWED_MINT_EXTENSIONS`.
+    /// `rules` selects the house rules every move in the game is validated against.
+    /// `team_mode` pairs opposite seats of a four-player table into partnerships.
+    /// The creator's `PlayerProfile` is created on their first game.
+    #[allow(clippy::too_many_arguments)]
     pub fn initialize_game(
         ctx: Context<InitializeGame>,
         max_players: u8,
         bet_amount: u64,
-        game_seed: String,
+        lobby_page: u32,
+        commitment: [u8; 32],
+        oracle: Option<Pubkey>,
+        rules: RuleSet,
+        team_mode: TeamMode,
     ) -> Result<()> {
+        let config = &ctx.accounts.config;
         require!(
-            max_players == 2 || max_players == 4,
+            config.allowed_player_counts.contains(&max_players),
             ErrorCode::InvalidPlayerCount
         );
-        require!(bet_amount > 0, ErrorCode::InvalidBetAmount);
+        require!(
+            bet_amount > 0 && bet_amount >= config.min_bet && bet_amount <= config.max_bet,
+            ErrorCode::InvalidBetAmount
+        );
+        require!(commitment != [0u8; 32], ErrorCode::MissingCommitment);
+        if let Some(oracle) = oracle {
+            require!(
+                config.oracles.contains(&oracle),
+                ErrorCode::OracleNotAllowed
+            );
+        }
+        if let Some(mint) = &ctx.accounts.mint {
+            check_mint_extensions(&mint.to_account_info().try_borrow_data()?)?;
+        }
+        let layout = BoardLayout::for_seats(max_players).ok_or(ErrorCode::InvalidPlayerCount)?;
+        require!(
+            team_mode == TeamMode::Solo || max_players == 4,
+            ErrorCode::TeamModeNeedsFourPlayers
+        );
 
-        let game = &mut ctx.accounts.game;
+        let fee_bps = config.fee_bps;
+        let turn_timeout = config.turn_timeout;
+        let max_missed_turns = config.max_missed_turns;
+        let mint = ctx.accounts.mint.as_ref().map(|mint| mint.key());
+        let game_key = ctx.accounts.game.key();
         let creator = ctx.accounts.creator.key();
         let bump = ctx.bumps.game;
 
-        game.creator = creator;
-        game.players = vec![creator];
-        game.max_players = max_players;
-        game.bet_amount = bet_amount;
-        game.total_bet = bet_amount; // Only count deposited bet amount
-        game.platform_fee = bet_amount / 10; // 10% fee
-        game.prize_pool = bet_amount - game.platform_fee;
-        game.track_length = 56; // Standard Ludo has 52 common squares + 6 home squares - 1 (starting at 0) = 57
-        game.positions = vec![vec![0; 4]]; // Each player has 4 pieces
-        game.home_counts = vec![0]; // Count of pieces that reached home
-        game.seed = game_seed;
-        game.bump = bump;
-
-        // Setup board coordinates based on player count
-        if max_players == 4 {
-            game.start_offsets = vec![0, 13, 26, 39]; // Starting positions for each player
-            game.home_entry_positions = vec![50, 11, 24, 37]; // Position before home path entry
-        } else {
-            game.start_offsets = vec![0, 26]; // For 2 players, positions opposite to each other
-            game.home_entry_positions = vec![50, 24];
-        }
-
-        // Safe zones (star positions) in standard Ludo
-        game.safe_zones = vec![8, 13, 21, 26, 34, 39, 47];
+        let counter = &mut ctx.accounts.game_counter;
+        let game_id = counter.next_id;
+        counter.next_id += 1;
+        counter.bump = ctx.bumps.game_counter;
+        let game_seed = game_id.to_le_bytes();
+
+        let lobby = &mut ctx.accounts.lobby;
+        lobby.mint = mint.unwrap_or_default();
+        lobby.bet_amount = bet_amount;
+        lobby.max_players = max_players;
+        lobby.page = lobby_page;
+        lobby.bump = ctx.bumps.lobby;
+        require!(
+            lobby.list(LobbyEntry {
+                game: game_key,
+                player_count: 1,
+                rules: rules.to_flags(),
+                team_mode: team_mode as u8,
+            }),
+            ErrorCode::LobbyFull
+        );
 
-        game.turn = 0;
-        game.last_move_time = Clock::get()?.unix_timestamp;
-        game.winner = None;
-        game.second_place = None;
-        game.game_state = GameStatus::WaitingForPlayers;
-        game.dice_roll = None;
-        game.consecutive_sixes = 0; // Track consecutive sixes for standard rule
-        game.missed_turns = vec![0];
-        game.deposited_players = vec![creator];
+        {
+            let mut game = ctx.accounts.game.load_init()?;
+            game.creator = creator;
+            game.players[0] = creator;
+            game.deposited_players[0] = creator;
+            game.player_count = 1;
+            game.max_players = max_players;
+            game.bet_amount = bet_amount;
+            game.total_bet = bet_amount; // Only count deposited bet amount
+            game.fee_bps = fee_bps; // Fee and timeouts are locked in from the config at creation
+            game.turn_timeout = turn_timeout;
+            game.max_missed_turns = max_missed_turns;
+            game.platform_fee = bps_of(bet_amount, fee_bps);
+            game.prize_pool = bet_amount - game.platform_fee;
+            game.seed[..game_seed.len()].copy_from_slice(&game_seed);
+            game.seed_len = game_seed.len() as u8;
+            game.bump = bump;
+            game.mint = mint.unwrap_or_default();
+            game.rules = rules.to_flags();
+            game.team_mode = team_mode as u8;
+            game.history = 1;
+            game.profiles = 1;
+            game.listed = 1;
+            game.version = GameState::VERSION;
+
+            // Setup board coordinates based on player count
+            game.track_squares = layout.track_squares;
+            game.track_length = layout.finish();
+            game.start_offsets = layout.start_offsets;
+            game.safe_zones = layout.safe_squares; // Stars, start squares go by the rule set
+            game.safe_zone_count = layout.safe_count;
+
+            game.turn = 0;
+            game.last_move_time = Clock::get()?.unix_timestamp;
+            game.set_status(GameStatus::WaitingForPlayers);
+            game.commitments[0] = commitment;
+            game.oracle = oracle.unwrap_or_default();
+            game.entropy = hashv(&[game_seed.as_ref(), creator.as_ref()]).to_bytes();
+        }
+        ctx.accounts.history.load_init()?.game = game_key;
+        ctx.accounts.profile.record_join(creator, ctx.bumps.profile, bet_amount);
+
+        emit!(GameCreated {
+            game: game_key,
+            game_id,
+            creator,
+            max_players,
+            bet_amount,
+            mint,
+            oracle,
+            rules,
+            team_mode,
+            lobby_page,
+        });
+
+        if mint.is_some() {
+            // Transfer bet tokens from creator into the game vault
+            let vault = TokenVault::from_accounts(
+                &ctx.accounts.token_program,
+                &ctx.accounts.vault,
+                &ctx.accounts.mint,
+                ctx.accounts.game.to_account_info(),
+            )?;
+            let from = ctx
+                .accounts
+                .creator_token_account
+                .as_ref()
+                .ok_or(ErrorCode::TokenAccountsMissing)?;
+            vault.deposit(from, &ctx.accounts.creator, bet_amount)?;
+            return Ok(());
+        }
 
         // Transfer bet amount from creator to game account
         let transfer_instruction = system_instruction::transfer(
@@ -84,554 +259,2592 @@ pub mod ludo_game {
     }
 
     /// Join an existing game.
-    pub fn join_game(ctx: Context<JoinGame>) -> Result<()> {
+    ///
+    /// `commitment` is the head of the joining player's dice hash chain.
+    /// The player's `PlayerProfile` is created on their first game. A listed game
+    /// leaves its lobby once the last seat is taken.
+    pub fn join_game(ctx: Context<JoinGame>, commitment: [u8; 32]) -> Result<()> {
         let player = ctx.accounts.player.key();
         let game_key = ctx.accounts.game.key();
-        let bet_amount = ctx.accounts.game.bet_amount;
-        
+        let (bet_amount, mint) = {
+            let game = ctx.accounts.game.load()?;
+            require!(commitment != [0u8; 32], ErrorCode::MissingCommitment);
+            require!(
+                game.status() == GameStatus::WaitingForPlayers,
+                ErrorCode::GameAlreadyStarted
+            );
+            require!(game.player_count < game.max_players, ErrorCode::GameFull);
+            require!(game.seat_of(&player).is_none(), ErrorCode::AlreadyJoined);
+            (game.bet_amount, game.mint())
+        };
+        ctx.accounts.profile.record_join(player, ctx.bumps.profile, bet_amount);
+
+        if mint.is_some() {
+            // Transfer bet tokens from player into the game vault
+            let vault = TokenVault::from_accounts(
+                &ctx.accounts.token_program,
+                &ctx.accounts.vault,
+                &ctx.accounts.mint,
+                ctx.accounts.game.to_account_info(),
+            )?;
+            let from = ctx
+                .accounts
+                .player_token_account
+                .as_ref()
+                .ok_or(ErrorCode::TokenAccountsMissing)?;
+            vault.deposit(from, &ctx.accounts.player, bet_amount)?;
+        } else {
+            // Transfer bet amount from player to game account
+            let transfer_instruction = system_instruction::transfer(
+                &ctx.accounts.player.key(),
+                &game_key,
+                bet_amount,
+            );
+
+            invoke(
+                &transfer_instruction,
+                &[
+                    ctx.accounts.player.to_account_info().clone(),
+                    ctx.accounts.game.to_account_info().clone(),
+                    ctx.accounts.system_program.to_account_info().clone(),
+                ],
+            )?;
+        }
+
+        let mut game = ctx.accounts.game.load_mut()?;
+        let seat = game.player_count as usize;
+        game.players[seat] = player;
+        game.deposited_players[seat] = player;
+        game.commitments[seat] = commitment;
+        game.player_count += 1;
+        game.entropy = hashv(&[game.entropy.as_ref(), player.as_ref()]).to_bytes();
+        game.total_bet += game.bet_amount;
+        game.platform_fee = bps_of(game.total_bet, game.fee_bps);
+        game.prize_pool = game.total_bet - game.platform_fee;
+
+        emit!(PlayerJoined {
+            game: game_key,
+            player,
+            seat: seat as u8,
+            total_bet: game.total_bet,
+        });
+
+        if game.player_count == game.max_players {
+            let clock = Clock::get()?;
+            game.set_status(GameStatus::Active);
+            game.last_move_time = clock.unix_timestamp;
+            request_next_roll(&mut game, clock.slot);
+        }
+
+        if let Some(lobby) = listed_lobby(&game, game_key, &mut ctx.accounts.lobby)? {
+            if game.status() == GameStatus::Active {
+                lobby.remove(game_key);
+                game.listed = 0;
+            } else {
+                lobby.update(game_key, game.player_count);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Roll the dice using commit-reveal.
+    ///
+    /// Every player commits to the head `H^n(secret)` of a sha256 hash chain when they
+    /// take their seat. Each roll the current player reveals the preimage of their
+    /// last commitment, which becomes their new commitment. The reveal is mixed with
+    /// the running game entropy (which already contains every earlier reveal from
+    /// every seat) and the hash of the slot the roll was requested in, so the reveal
+    /// has to land in a later slot. The roll is requested as soon as the turn reaches
+    /// the player, so they can reveal in their first transaction of the turn. The
+    /// player knows their reveal ahead of time but not that slot hash, and once the
+    /// hash is known their commitment leaves them no other reveal to pick.
+    pub fn request_randomness(ctx: Context<SimpleRandomness>, reveal: [u8; 32]) -> Result<()> {
+        let game_key = ctx.accounts.game.key();
+        let mut game = ctx.accounts.game.load_mut()?;
         require!(
-            ctx.accounts.game.game_state == GameStatus::WaitingForPlayers,
-            ErrorCode::GameAlreadyStarted
+            game.status() == GameStatus::Active,
+            ErrorCode::GameNotActive
         );
+        require!(game.oracle().is_none(), ErrorCode::OracleRollRequired);
+        let player = acting_player(&ctx.accounts.player, &ctx.accounts.session, game_key)?;
         require!(
-            (ctx.accounts.game.players.len() as u8) < ctx.accounts.game.max_players,
-            ErrorCode::GameFull
+            player == game.players[game.turn as usize],
+            ErrorCode::NotYourTurn
         );
-        require!(!ctx.accounts.game.players.contains(&player), ErrorCode::AlreadyJoined);
+        require!(game.dice_roll().is_none(), ErrorCode::DiceAlreadyRolled);
 
-        // Transfer bet amount from player to game account
-        let transfer_instruction = system_instruction::transfer(
-            &ctx.accounts.player.key(),
-            &game_key,
-            bet_amount,
+        reserve_history(
+            &game,
+            &ctx.accounts.history,
+            &ctx.accounts.player,
+            &ctx.accounts.system_program,
+        )?;
+        let mut history = open_history(&game, &ctx.accounts.history, HISTORY_TURN_ENTRIES)?;
+        let clock = Clock::get()?;
+        let slot_hash = requested_slot_hash(&mut game, &ctx.accounts.recent_slothashes)?;
+        roll_from_reveal(
+            &mut game,
+            game_key,
+            reveal,
+            slot_hash,
+            clock.unix_timestamp,
+            history.as_mut(),
+        )?;
+        request_next_roll(&mut game, clock.slot);
+        Ok(())
+    }
+
+    /// Reveal a requested commit-reveal roll and play it in the same transaction.
+    ///
+    /// `priorities` lists piece indices in the order the player prefers them; the
+    /// first one with a legal move for the roll is played. If none of them can move,
+    /// the lowest-numbered piece that can is played instead, since a legal move may
+    /// not be declined. Rolls that grant another turn leave it with the player as
+    /// usual, so they call this again from the next slot. Profiles are credited as in
+    /// `move_piece`.
+    pub fn roll_and_move<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SimpleRandomness<'info>>,
+        reveal: [u8; 32],
+        priorities: Vec<u8>,
+    ) -> Result<()> {
+        let game_key = ctx.accounts.game.key();
+        let mut game = ctx.accounts.game.load_mut()?;
+        require!(
+            game.status() == GameStatus::Active,
+            ErrorCode::GameNotActive
+        );
+        require!(game.oracle().is_none(), ErrorCode::OracleRollRequired);
+        let player = acting_player(&ctx.accounts.player, &ctx.accounts.session, game_key)?;
+        require!(
+            player == game.players[game.turn as usize],
+            ErrorCode::NotYourTurn
+        );
+        require!(game.dice_roll().is_none(), ErrorCode::DiceAlreadyRolled);
+        require!(
+            priorities.len() <= PIECES_PER_PLAYER,
+            ErrorCode::InvalidPiece
         );
 
-        invoke(
-            &transfer_instruction,
-            &[
-                ctx.accounts.player.to_account_info().clone(),
-                ctx.accounts.game.to_account_info().clone(),
-                ctx.accounts.system_program.to_account_info().clone(),
-            ],
+        reserve_history(
+            &game,
+            &ctx.accounts.history,
+            &ctx.accounts.player,
+            &ctx.accounts.system_program,
+        )?;
+        let mut history = open_history(&game, &ctx.accounts.history, HISTORY_TURN_ENTRIES)?;
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        let slot_hash = requested_slot_hash(&mut game, &ctx.accounts.recent_slothashes)?;
+        roll_from_reveal(
+            &mut game,
+            game_key,
+            reveal,
+            slot_hash,
+            now,
+            history.as_mut(),
         )?;
-        
-        let game = &mut ctx.accounts.game;
-        game.players.push(player);
-        game.deposited_players.push(player);
-        game.positions.push(vec![0; 4]);
-        game.home_counts.push(0);
-        game.missed_turns.push(0);
-        game.total_bet += game.bet_amount;
-        game.prize_pool = game.total_bet - (game.total_bet / 10);
-
-        if (game.players.len() as u8) == game.max_players {
-            game.game_state = GameStatus::Active;
-            game.last_move_time = Clock::get()?.unix_timestamp;
-        }
 
-        Ok(())
+        // The roll was forfeited or had no legal move at all
+        let Some(dice_value) = game.dice_roll() else {
+            request_next_roll(&mut game, clock.slot);
+            return Ok(());
+        };
+
+        let legal = game.board().legal_moves(game.moving_seat(), dice_value);
+        let piece = priorities
+            .iter()
+            .copied()
+            .find(|piece| legal.contains_piece(*piece))
+            .or_else(|| legal.as_slice().first().map(|mv| mv.piece))
+            .ok_or(ErrorCode::NoValidMoves)?;
+        let outcome = play_piece(
+            &mut game,
+            game_key,
+            player,
+            piece,
+            now,
+            history.as_mut(),
+        )?;
+        request_next_roll(&mut game, clock.slot);
+        record_move_stats(
+            &game,
+            &outcome,
+            player,
+            &mut ctx.accounts.profile,
+            ctx.remaining_accounts,
+        )
     }
 
-    /// Simple dice roll (without Switchboard VRF).
-    pub fn request_randomness(ctx: Context<SimpleRandomness>) -> Result<()> {
-        let game = &mut ctx.accounts.game;
+    /// Ask for a dice roll, fixing the slot the roll will be drawn from.
+    ///
+    /// Oracle games have the roll applied once the oracle calls back into
+    /// `fulfill_randomness`. Commit-reveal games reveal in a later slot through
+    /// `request_randomness` or `roll_and_move`, within `ROLL_REQUEST_WINDOW_SLOTS`.
+    /// Their rolls are requested as the turn passes, so only a seat that registered
+    /// its hash chain after the turn reached it has to ask here.
+    pub fn request_roll(ctx: Context<RequestRoll>) -> Result<()> {
+        let game_key = ctx.accounts.game.key();
+        let player = acting_player(&ctx.accounts.player, &ctx.accounts.session, game_key)?;
+        let mut game = ctx.accounts.game.load_mut()?;
         require!(
-            game.game_state == GameStatus::Active,
+            game.status() == GameStatus::Active,
             ErrorCode::GameNotActive
         );
         require!(
-            ctx.accounts.player.key() == game.players[game.turn as usize],
+            player == game.players[game.turn as usize],
             ErrorCode::NotYourTurn
         );
+        require!(game.dice_roll().is_none(), ErrorCode::DiceAlreadyRolled);
+        require!(
+            game.randomness_requested == 0,
+            ErrorCode::RandomnessAlreadyRequested
+        );
+        require!(
+            game.oracle().is_some() || game.commitments[game.turn as usize] != [0u8; 32],
+            ErrorCode::MissingCommitment
+        );
 
+        reserve_history(
+            &game,
+            &ctx.accounts.history,
+            &ctx.accounts.player,
+            &ctx.accounts.system_program,
+        )?;
         let clock = Clock::get()?;
-        if clock.unix_timestamp - game.last_move_time > 60 {
-            let turn = game.turn;
-            game.missed_turns[turn as usize] += 1;
-            msg!("Player {} missed their turn!", turn);
-
-            if game.missed_turns[turn as usize] >= 3 {
-                msg!("Player {} removed due to inactivity", turn);
-                // Mark player as inactive but keep their position in the array
-                game.players[turn as usize] = Pubkey::default();
-
-                let active_players: Vec<&Pubkey> = game
-                    .players
-                    .iter()
-                    .filter(|&&p| p != Pubkey::default())
-                    .collect();
-                if active_players.len() == 1 {
-                    msg!("Only one player left, ending game.");
-                    game.winner = Some(*active_players[0]);
-                    game.game_state = GameStatus::Completed;
-                    return Ok(());
-                }
-            }
-            game.consecutive_sixes = 0; // Reset consecutive sixes count
-            game.turn = next_active_player(game, game.turn);
-            return Err(ErrorCode::TurnSkipped.into());
-        }
-
-        // Simple dice roll using slot and timestamp as randomness source (for testing)
-        let slot = clock.slot;
-        let timestamp = clock.unix_timestamp;
-        let random_value = ((slot as u64).wrapping_mul(timestamp as u64) % 6) as u8 + 1;
-        msg!("Player {} rolled a {}", game.turn, random_value);
-
-        // Track consecutive sixes (standard Ludo rule)
-        if random_value == 6 {
-            game.consecutive_sixes += 1;
-            if game.consecutive_sixes >= 3 {
-                msg!(
-                    "Player {} rolled three consecutive sixes, turn is forfeited",
-                    game.turn
-                );
-                game.consecutive_sixes = 0;
-                game.turn = next_active_player(game, game.turn);
-                game.last_move_time = clock.unix_timestamp;
-                return Ok(());
-            }
-        } else {
-            game.consecutive_sixes = 0;
-        }
-
-        game.dice_roll = Some(random_value);
+        game.randomness_requested = 1;
+        game.randomness_request_slot = clock.slot;
         game.last_move_time = clock.unix_timestamp;
 
         Ok(())
     }
 
-    /// Move one of the player's pieces based on the last dice roll.
-    pub fn move_piece(ctx: Context<MovePiece>, piece_index: u8) -> Result<()> {
-        let game = &mut ctx.accounts.game;
+    /// Oracle callback that consumes VRF output and sets `dice_roll`.
+    ///
+    /// Must land after the slot of the request and within `ROLL_REQUEST_WINDOW_SLOTS`
+    /// of it, otherwise the request lapses and the turn times out.
+    pub fn fulfill_randomness(
+        ctx: Context<FulfillRandomness>,
+        randomness: [u8; 32],
+    ) -> Result<()> {
+        let oracle = SignedCallbackOracle {
+            authority: &ctx.accounts.oracle_authority,
+            randomness,
+        };
+        let game_key = ctx.accounts.game.key();
+        let mut game = ctx.accounts.game.load_mut()?;
         require!(
-            game.game_state == GameStatus::Active,
+            game.status() == GameStatus::Active,
             ErrorCode::GameNotActive
         );
 
-        let current_player = game.turn as usize;
-        require!(
-            ctx.accounts.player.key() == game.players[current_player],
-            ErrorCode::NotYourTurn
+        let randomness = oracle.consume(&game)?;
+        take_roll_request(&mut game)?;
+        game.entropy = hashv(&[game.entropy.as_ref(), randomness.as_ref()]).to_bytes();
+        let random_value = dice_from_entropy(&randomness);
+        let mut history = open_history(&game, &ctx.accounts.history, HISTORY_TURN_ENTRIES)?;
+        apply_dice_roll(
+            &mut game,
+            game_key,
+            random_value,
+            Clock::get()?.unix_timestamp,
+            history.as_mut(),
         );
-        require!((piece_index as usize) < 4, ErrorCode::InvalidPiece);
 
-        let dice_value = game.dice_roll.ok_or(ErrorCode::DiceNotRolled)?;
-        let current_pos = game.positions[current_player][piece_index as usize];
+        Ok(())
+    }
+
+    /// Replace an exhausted hash chain with a fresh one.
+    ///
+    /// The player reveals the preimage of their current commitment to prove continuity.
+    /// Not allowed while the player holds the turn, so a new chain can never be picked
+    /// after the entropy for the player's next roll is already known, nor while any
+    /// roll is pending, since the reveal mixed in here would change its outcome. Seats
+    /// of games migrated from the Borsh layout register their first chain without a
+    /// reveal.
+    pub fn rotate_commitment(
+        ctx: Context<RotateCommitment>,
+        reveal: [u8; 32],
+        commitment: [u8; 32],
+    ) -> Result<()> {
+        let mut game = ctx.accounts.game.load_mut()?;
+        require!(
+            game.status() == GameStatus::WaitingForPlayers
+                || game.status() == GameStatus::Active,
+            ErrorCode::GameOver
+        );
+        require!(commitment != [0u8; 32], ErrorCode::MissingCommitment);
+
+        let seat = game
+            .seat_of(&ctx.accounts.player.key())
+            .ok_or(ErrorCode::NotAPlayer)?;
+        // Seats migrated from the Borsh layout have no chain yet and register their
+        // first one here. `request_roll` refuses them until then, so no roll can be
+        // pending that the new chain could be picked against.
+        if game.commitments[seat] == [0u8; 32] {
+            game.commitments[seat] = commitment;
+            return Ok(());
+        }
+        require!(
+            game.status() != GameStatus::Active || seat != game.turn as usize,
+            ErrorCode::CommitmentLocked
+        );
+        require!(
+            game.randomness_requested == 0,
+            ErrorCode::RandomnessAlreadyRequested
+        );
+        require!(reveal != [0u8; 32], ErrorCode::RevealMissing);
+        require!(
+            hash(&reveal).to_bytes() == game.commitments[seat],
+            ErrorCode::RevealMismatch
+        );
 
-        // Check if player has any valid moves
-        let has_valid_move = check_for_valid_moves(game, current_player, dice_value);
-        require!(has_valid_move, ErrorCode::NoValidMoves);
+        game.entropy = hashv(&[game.entropy.as_ref(), reveal.as_ref()]).to_bytes();
+        game.commitments[seat] = commitment;
+        Ok(())
+    }
 
-        // Handle piece movement logic based on standard Ludo rules
-        if current_pos == 0 {
-            // Can only move piece out of home yard with a 6
-            if dice_value != 6 {
-                return Err(ErrorCode::CannotStart.into());
-            }
-            // Start piece from base at the player's starting position
-            game.positions[current_player][piece_index as usize] = 1;
-            msg!(
-                "Player {} started piece {} with a 6",
-                current_player,
-                piece_index
+    /// Authorize an ephemeral session key to roll and move for the player in one
+    /// game until `expires_at`, so the app doesn't need a wallet approval per turn.
+    ///
+    /// `max_spend` lamports are moved from the player to the session key to pay its
+    /// transaction fees; that is all the key can ever spend of the player's funds.
+    pub fn create_session(
+        ctx: Context<CreateSession>,
+        expires_at: i64,
+        max_spend: u64,
+    ) -> Result<()> {
+        let game_key = ctx.accounts.game.key();
+        let player = ctx.accounts.player.key();
+        let signer = ctx.accounts.session_signer.key();
+        {
+            let game = ctx.accounts.game.load()?;
+            require!(
+                game.status() == GameStatus::WaitingForPlayers
+                    || game.status() == GameStatus::Active,
+                ErrorCode::GameOver
             );
-        } else {
-            // Calculate new position
-            let new_pos = current_pos + dice_value;
+            require!(game.seat_of(&player).is_some(), ErrorCode::NotAPlayer);
+        }
 
-            // Check if piece is entering home stretch
-            let home_entry = game.home_entry_positions[current_player];
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            expires_at > now && expires_at - now <= MAX_SESSION_SECONDS,
+            ErrorCode::InvalidSessionExpiry
+        );
+        require_keys_neq!(signer, player, ErrorCode::InvalidSession);
+
+        let session = &mut ctx.accounts.session;
+        session.player = player;
+        session.game = game_key;
+        session.signer = signer;
+        session.expires_at = expires_at;
+        session.max_spend = max_spend;
+        session.bump = ctx.bumps.session;
+
+        if max_spend > 0 {
+            // Fund the session key's transaction fees
+            let transfer_instruction = system_instruction::transfer(&player, &signer, max_spend);
+            invoke(
+                &transfer_instruction,
+                &[
+                    ctx.accounts.player.to_account_info().clone(),
+                    ctx.accounts.session_signer.to_account_info().clone(),
+                    ctx.accounts.system_program.to_account_info().clone(),
+                ],
+            )?;
+        }
 
-            if current_pos <= home_entry && new_pos > home_entry {
-                // Piece is entering home path
-                let home_steps = new_pos - home_entry;
+        emit!(SessionCreated {
+            game: game_key,
+            player,
+            signer,
+            expires_at,
+            max_spend,
+        });
+        Ok(())
+    }
 
-                // Verify home path position is valid (not overshooting)
-                if home_steps > 6 {
-                    return Err(ErrorCode::InvalidMove.into());
-                }
+    /// Revoke a session key before it expires and reclaim the session account's rent
+    pub fn revoke_session(ctx: Context<RevokeSession>) -> Result<()> {
+        let session = &ctx.accounts.session;
+        emit!(SessionRevoked {
+            game: session.game,
+            player: session.player,
+            signer: session.signer,
+        });
+        Ok(())
+    }
 
-                // Set position into home section (51-56 range for home path)
-                let home_position = 50 + home_steps;
-                game.positions[current_player][piece_index as usize] = home_position;
-
-                // Check if piece reached home (position 56)
-                if home_position == 56 {
-                    msg!(
-                        "Player {}'s piece {} reached home!",
-                        current_player,
-                        piece_index
-                    );
-                    game.home_counts[current_player] += 1;
-
-                    // Check if all 4 pieces are home
-                    if game.home_counts[current_player] == 4 {
-                        msg!("Player {} finished all pieces!", current_player);
-                        if game.max_players == 2 {
-                            game.winner = Some(ctx.accounts.player.key());
-                            game.game_state = GameStatus::Completed;
-                        } else {
-                            if game.winner.is_none() {
-                                game.winner = Some(ctx.accounts.player.key());
-                            } else if game.second_place.is_none()
-                                && game.winner.unwrap() != ctx.accounts.player.key()
-                            {
-                                game.second_place = Some(ctx.accounts.player.key());
-                                game.game_state = GameStatus::Completed;
-                            }
-                        }
-                    }
-                }
-            } else if new_pos > 50 {
-                // Regular movement but would exceed track length
-                return Err(ErrorCode::InvalidMove.into());
-            } else {
-                // Regular movement on main track
-                game.positions[current_player][piece_index as usize] = new_pos;
-                msg!(
-                    "Player {} moved piece {} from {} to {}",
-                    current_player,
-                    piece_index,
-                    current_pos,
-                    new_pos
-                );
-
-                // Handle capture logic
-                let player_offset = game.start_offsets[current_player];
-                let absolute_pos = (player_offset + new_pos) % 52;
-
-                if !game.safe_zones.contains(&absolute_pos) {
-                    for opp_index in 0..game.players.len() {
-                        if opp_index == current_player
-                            || game.players[opp_index] == Pubkey::default()
-                        {
-                            continue;
-                        }
-
-                        let opp_offset = game.start_offsets[opp_index];
-                        for opp_piece_idx in 0..4 {
-                            let opp_pos = game.positions[opp_index][opp_piece_idx];
-                            if opp_pos > 0 && opp_pos <= 50 {
-                                // Only pieces on main track can be captured
-                                let opp_abs = (opp_offset + opp_pos) % 52;
-                                if opp_abs == absolute_pos {
-                                    msg!(
-                                        "Player {}'s piece captured opponent {}'s piece {}",
-                                        current_player,
-                                        opp_index,
-                                        opp_piece_idx
-                                    );
-                                    game.positions[opp_index][opp_piece_idx] = 0;
-                                    // Reset to home yard
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    /// Skip the current player's turn once they have been idle longer than the
+    /// game's turn timeout. Permissionless so any participant or a keeper can keep
+    /// a stalled game moving; the idle player is ejected after too many misses.
+    pub fn claim_timeout(ctx: Context<ClaimTimeout>) -> Result<()> {
+        let game_key = ctx.accounts.game.key();
+        let mut game = ctx.accounts.game.load_mut()?;
+        require!(
+            game.status() == GameStatus::Active,
+            ErrorCode::GameNotActive
+        );
+
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        require!(
+            now - game.last_move_time > game.turn_timeout,
+            ErrorCode::TurnNotTimedOut
+        );
+
+        reserve_history(
+            &game,
+            &ctx.accounts.history,
+            &ctx.accounts.caller,
+            &ctx.accounts.system_program,
+        )?;
+        let mut history = open_history(&game, &ctx.accounts.history, HISTORY_TURN_ENTRIES)?;
+        let turn = game.turn;
+        game.missed_turns[turn as usize] += 1;
+        emit!(TurnSkipped {
+            game: game_key,
+            seat: turn,
+            reason: TurnSkipReason::Timeout,
+        });
+        if let Some(history) = history.as_mut() {
+            history.push(HistoryEntry::skip(turn, TurnSkipReason::Timeout, now));
         }
 
-        // Update turn - player gets another turn if rolled a 6 (unless 3 sixes in a row)
-        if dice_value != 6 && game.game_state == GameStatus::Active {
-            game.turn = next_active_player(game, game.turn);
+        if game.missed_turns[turn as usize] >= game.max_missed_turns {
+            emit!(PlayerEjected {
+                game: game_key,
+                player: game.players[turn as usize],
+                seat: turn,
+            });
+            eliminate_player(&mut game, game_key, turn as usize, now, history.as_mut());
+            if game.status() == GameStatus::Completed {
+                return Ok(());
+            }
         }
 
-        game.dice_roll = None;
-        game.last_move_time = Clock::get()?.unix_timestamp;
+        game.consecutive_sixes = 0; // Reset consecutive sixes count
+        game.dice_roll = 0;
+        game.randomness_requested = 0;
+        game.turn = next_active_player(&game, game.turn);
+        game.last_move_time = now;
+        request_next_roll(&mut game, clock.slot);
+        Ok(())
+    }
+
+    /// Concede the game. The player is marked inactive the same way an idle player
+    /// is ejected, and final placings are awarded once one contender is left.
+    pub fn resign(ctx: Context<Resign>) -> Result<()> {
+        let game_key = ctx.accounts.game.key();
+        let mut game = ctx.accounts.game.load_mut()?;
+        require!(
+            game.status() == GameStatus::Active,
+            ErrorCode::GameNotActive
+        );
+
+        let player = ctx.accounts.player.key();
+        let seat = game.seat_of(&player).ok_or(ErrorCode::NotAPlayer)?;
+        require!(
+            game.winner() != Some(player) && game.second_place() != Some(player),
+            ErrorCode::AlreadyPlaced
+        );
+
+        emit!(PlayerResigned {
+            game: game_key,
+            player,
+            seat: seat as u8,
+        });
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        reserve_history(
+            &game,
+            &ctx.accounts.history,
+            &ctx.accounts.player,
+            &ctx.accounts.system_program,
+        )?;
+        let mut history = open_history(&game, &ctx.accounts.history, 1)?;
+        eliminate_player(&mut game, game_key, seat, now, history.as_mut());
+
+        if game.status() == GameStatus::Active && game.turn as usize == seat {
+            game.consecutive_sixes = 0;
+            game.dice_roll = 0;
+            game.randomness_requested = 0;
+            game.turn = next_active_player(&game, game.turn);
+            game.last_move_time = now;
+            request_next_roll(&mut game, clock.slot);
+        }
         Ok(())
     }
 
+    /// Move one of the player's pieces based on the last dice roll.
+    ///
+    /// In a partnership where finished players play on, a player whose pieces are
+    /// all home moves their partner's pieces instead.
+    ///
+    /// Takes the profiles of any players the move captures as writable remaining
+    /// accounts, so their captures suffered are counted. Games created before
+    /// profiles existed may leave them and the mover's profile out.
+    pub fn move_piece<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MovePiece<'info>>,
+        piece_index: u8,
+    ) -> Result<()> {
+        let game_key = ctx.accounts.game.key();
+        let mut game = ctx.accounts.game.load_mut()?;
+        require!(
+            game.status() == GameStatus::Active,
+            ErrorCode::GameNotActive
+        );
+
+        let player = acting_player(&ctx.accounts.player, &ctx.accounts.session, game_key)?;
+        require!(
+            player == game.players[game.turn as usize],
+            ErrorCode::NotYourTurn
+        );
+        let mut history = open_history(&game, &ctx.accounts.history, 1)?;
+        let clock = Clock::get()?;
+        let outcome = play_piece(
+            &mut game,
+            game_key,
+            player,
+            piece_index,
+            clock.unix_timestamp,
+            history.as_mut(),
+        )?;
+        request_next_roll(&mut game, clock.slot);
+        record_move_stats(
+            &game,
+            &outcome,
+            player,
+            &mut ctx.accounts.profile,
+            ctx.remaining_accounts,
+        )
+    }
+
     /// Distribute prizes to winners
-    pub fn distribute_prizes(ctx: Context<DistributePrizes>) -> Result<()> {
+    ///
+    /// Takes one writable remaining account per deposited player, in the same order
+    /// as `deposited_players`: the player's profile. Every profile is credited with
+    /// its placing and winnings and re-rated by finishing order. Games created before
+    /// profiles existed may pass none and go unrated.
+    pub fn distribute_prizes<'info>(
+        ctx: Context<'_, '_, 'info, 'info, DistributePrizes<'info>>,
+    ) -> Result<()> {
         // Gather all required values upfront
-        let game_state = ctx.accounts.game.game_state;
-        let seed = ctx.accounts.game.seed.clone();
-        let bump = ctx.accounts.game.bump;
         let game_key = ctx.accounts.game.key();
+        let (
+            game_state,
+            seed,
+            seed_len,
+            bump,
+            total_bet,
+            paid_places,
+            team_mode,
+            fee_bps,
+            mint,
+        ) = {
+            let game = ctx.accounts.game.load()?;
+            (
+                game.status(),
+                game.seed,
+                game.seed_len as usize,
+                game.bump,
+                game.total_bet,
+                game.paid_places(),
+                game.team_mode(),
+                game.fee_bps,
+                game.mint(),
+            )
+        };
         let platform_key = ctx.accounts.platform.key();
-        let total_bet = ctx.accounts.game.total_bet;
-        let max_players = ctx.accounts.game.max_players;
-        let platform_fee = total_bet / 10;
-        
+        let platform_fee = bps_of(total_bet, fee_bps);
+
         // Get winner info before mutable borrow
-        let winner_opt = ctx.accounts.game.winner;
-        let second_place_opt = ctx.accounts.game.second_place;
-        
+        let (winner_opt, second_place_opt, deposited_players, player_count, ranks, rated) = {
+            let game = ctx.accounts.game.load()?;
+            (
+                game.winner(),
+                game.second_place(),
+                game.deposited_players,
+                game.player_count as usize,
+                game.finishing_ranks(),
+                game.profiles != 0,
+            )
+        };
+
         // Clone account infos
         let game_acct_info = ctx.accounts.game.to_account_info().clone();
         let platform_acct_info = ctx.accounts.platform.to_account_info().clone();
-        let system_program_info = ctx.accounts.system_program.to_account_info().clone();
-        
+
         require!(
             game_state == GameStatus::Completed,
             ErrorCode::GameNotCompleted
         );
 
-        let seeds = [b"game".as_ref(), seed.as_bytes(), &[bump]];
+        let seeds = [b"game".as_ref(), &seed[..seed_len], &[bump]];
         let signer = &[&seeds[..]];
 
-        // Transfer platform fee
-        invoke_signed(
-            &system_instruction::transfer(&game_key, &platform_key, platform_fee),
-            &[
-                game_acct_info.clone(),
-                platform_acct_info,
-                system_program_info.clone(),
-            ],
-            signer,
-        )?;
-        
-        // Distribute prizes based on game mode
-        if max_players == 2 {
+        // Distribute prizes based on the places the table pays
+        let payouts = if team_mode != TeamMode::Solo {
+            // A winning partnership splits the pot between its two players
+            let first_partner = winner_opt.ok_or(ErrorCode::NoWinner)?;
+            let second_partner = second_place_opt.ok_or(ErrorCode::NoSecondPlace)?;
+            let pot = total_bet - platform_fee;
+            vec![(first_partner, pot - pot / 2), (second_partner, pot / 2)]
+        } else if paid_places == 1 {
             let winner = winner_opt.ok_or(ErrorCode::NoWinner)?;
-            let winner_amount = total_bet - platform_fee;
-
-            invoke_signed(
-                &system_instruction::transfer(&game_key, &winner, winner_amount),
-                &[
-                    game_acct_info.clone(),
-                    system_program_info.clone(),
-                ],
-                signer,
-            )?;
+            vec![(winner, total_bet - platform_fee)]
         } else {
             let first_place = winner_opt.ok_or(ErrorCode::NoWinner)?;
             let second_place = second_place_opt.ok_or(ErrorCode::NoSecondPlace)?;
-
-            let first_amount = (total_bet * 65) / 100;
-            let second_amount = (total_bet * 25) / 100;
-
-            invoke_signed(
-                &system_instruction::transfer(&game_key, &first_place, first_amount),
-                &[
-                    game_acct_info.clone(),
-                    system_program_info.clone(),
-                ],
-                signer,
-            )?;
-
-            invoke_signed(
-                &system_instruction::transfer(&game_key, &second_place, second_amount),
-                &[
-                    game_acct_info.clone(),
-                    system_program_info.clone(),
-                ],
-                signer,
+            let second_amount = bps_of(total_bet, SECOND_PLACE_BPS);
+            vec![
+                (first_place, total_bet - platform_fee - second_amount),
+                (second_place, second_amount),
+            ]
+        };
+
+        if mint.is_some() {
+            let vault = TokenVault::from_accounts(
+                &ctx.accounts.token_program,
+                &ctx.accounts.vault,
+                &ctx.accounts.mint,
+                game_acct_info,
             )?;
+            let prize_accounts = [
+                &ctx.accounts.winner_token_account,
+                &ctx.accounts.second_place_token_account,
+            ];
+
+            // Transfer platform fee
+            let platform_account = ctx
+                .accounts
+                .platform_token_account
+                .as_ref()
+                .ok_or(ErrorCode::TokenAccountsMissing)?;
+            vault.release(platform_account, platform_key, platform_fee, signer)?;
+
+            for ((recipient, amount), account) in payouts.iter().zip(prize_accounts) {
+                let account = account.as_ref().ok_or(ErrorCode::TokenAccountsMissing)?;
+                vault.release(account, *recipient, *amount, signer)?;
+            }
+        } else {
+            // Transfer platform fee
+            pay_from_game(&game_acct_info, &platform_acct_info, platform_fee)?;
+
+            // Recipient keys are checked against the game by the account constraints
+            pay_from_game(&game_acct_info, &ctx.accounts.winner, payouts[0].1)?;
+            if let Some(&(_, second_amount)) = payouts.get(1) {
+                let second_place = ctx
+                    .accounts
+                    .second_place
+                    .as_ref()
+                    .ok_or(ErrorCode::NoSecondPlace)?;
+                pay_from_game(&game_acct_info, second_place, second_amount)?;
+            }
         }
 
         // Now we can safely mutate the game state
-        let game = &mut ctx.accounts.game;
-        game.game_state = GameStatus::Finalized;
+        ctx.accounts
+            .game
+            .load_mut()?
+            .set_status(GameStatus::Finalized);
+
+        if rated || !ctx.remaining_accounts.is_empty() {
+            let deposited_players = &deposited_players[..player_count];
+            require!(
+                ctx.remaining_accounts.len() == deposited_players.len(),
+                ErrorCode::MissingProfile
+            );
+            let mut profiles = Vec::with_capacity(player_count);
+            for (player, info) in deposited_players.iter().zip(ctx.remaining_accounts) {
+                let profile = Account::<PlayerProfile>::try_from(info)?;
+                require_keys_eq!(profile.player, *player, ErrorCode::InvalidProfile);
+                PlayerProfile::verify_address(&profile)?;
+                profiles.push(profile);
+            }
+
+            let ratings: Vec<u32> = profiles.iter().map(|profile| profile.rating).collect();
+            let new_ratings = rating::rate_game(&ratings, &ranks[..player_count]);
+            for (seat, mut profile) in profiles.into_iter().enumerate() {
+                let player = Some(profile.player);
+                if player == winner_opt {
+                    profile.wins += 1;
+                } else if player == second_place_opt {
+                    // Partners share the win rather than placing second
+                    if team_mode == TeamMode::Solo {
+                        profile.second_places += 1;
+                    } else {
+                        profile.wins += 1;
+                    }
+                }
+                profile.total_won += payouts
+                    .iter()
+                    .filter(|(recipient, _)| Some(*recipient) == player)
+                    .map(|(_, amount)| amount)
+                    .sum::<u64>();
+                profile.rating = new_ratings[seat];
+                // Remaining accounts are not written back by Anchor
+                profile.exit(&crate::ID)?;
+            }
+        }
+
+        emit!(PrizesPaid {
+            game: game_key,
+            mint,
+            platform_fee,
+            winner: payouts[0].0,
+            winner_amount: payouts[0].1,
+            second_place: payouts.get(1).map(|payout| payout.0),
+            second_place_amount: payouts.get(1).map_or(0, |payout| payout.1),
+        });
         Ok(())
     }
 
-    /// Cancel a game that hasn't started yet and refund players
-    pub fn cancel_game(ctx: Context<CancelGame>) -> Result<()> {
-        require!(
-            ctx.accounts.game.game_state == GameStatus::WaitingForPlayers,
-            ErrorCode::GameAlreadyStarted
-        );
-        require!(
-            ctx.accounts.creator.key() == ctx.accounts.game.creator,
-            ErrorCode::NotGameCreator
-        );
-
-        let seeds = [b"game".as_ref(), ctx.accounts.game.seed.as_bytes(), &[ctx.accounts.game.bump]];
-        let signer = &[&seeds[..]];
+    /// Cancel a game that hasn't started yet, refund players and take it out of its lobby
+    ///
+    /// Takes one writable remaining account per deposited player, in the same order
+    /// as `deposited_players`: the player's wallet for SOL tables, or their token
+    /// account for token tables.
+    pub fn cancel_game<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CancelGame<'info>>,
+    ) -> Result<()> {
         let game_key = ctx.accounts.game.key();
-        let bet_amount = ctx.accounts.game.bet_amount;
-        let deposited_players = ctx.accounts.game.deposited_players.clone();
+        let (seed, seed_len, bump, bet_amount, deposited_players, player_count, mint) = {
+            let game = ctx.accounts.game.load()?;
+            require!(
+                game.status() == GameStatus::WaitingForPlayers,
+                ErrorCode::GameAlreadyStarted
+            );
+            require!(
+                ctx.accounts.creator.key() == game.creator,
+                ErrorCode::NotGameCreator
+            );
+            (
+                game.seed,
+                game.seed_len as usize,
+                game.bump,
+                game.bet_amount,
+                game.deposited_players,
+                game.player_count as usize,
+                game.mint(),
+            )
+        };
+
+        let seeds = [b"game".as_ref(), &seed[..seed_len], &[bump]];
+        let signer = &[&seeds[..]];
+        let deposited_players = &deposited_players[..player_count];
         let game_acct_info = ctx.accounts.game.to_account_info().clone();
-        let system_program_info = ctx.accounts.system_program.to_account_info().clone();
+
+        require!(
+            ctx.remaining_accounts.len() == deposited_players.len(),
+            ErrorCode::MissingPayoutAccount
+        );
 
         // Refund all players
-        for player in &deposited_players {
-            invoke_signed(
-                &system_instruction::transfer(&game_key, player, bet_amount),
+        if mint.is_some() {
+            let vault = TokenVault::from_accounts(
+                &ctx.accounts.token_program,
+                &ctx.accounts.vault,
+                &ctx.accounts.mint,
+                game_acct_info,
+            )?;
+            for (player, info) in deposited_players.iter().zip(ctx.remaining_accounts) {
+                let refund_account = InterfaceAccount::<TokenAccount>::try_from(info)?;
+                vault.release(&refund_account, *player, bet_amount, signer)?;
+            }
+        } else {
+            for (player, info) in deposited_players.iter().zip(ctx.remaining_accounts) {
+                require_keys_eq!(*info.key, *player, ErrorCode::MissingPayoutAccount);
+                pay_from_game(&game_acct_info, info, bet_amount)?;
+            }
+        }
+
+        let mut game = ctx.accounts.game.load_mut()?;
+        game.set_status(GameStatus::Cancelled);
+        if let Some(lobby) = listed_lobby(&game, game_key, &mut ctx.accounts.lobby)? {
+            lobby.remove(game_key);
+            game.listed = 0;
+        }
+
+        emit!(GameCancelled {
+            game: game_key,
+            refunded_players: deposited_players.len() as u8,
+            refund_amount: bet_amount,
+        });
+        Ok(())
+    }
+
+    /// Upgrade a game account created under an older layout to the current one.
+    ///
+    /// Resizes the account to the current size, topping up rent from `payer` when it
+    /// grows, then fills in fields older layouts did not have. The original Borsh
+    /// layout is larger than the current one and shrinks. Permissionless, since the
+    /// upgrade is fully determined by the account's contents.
+    pub fn migrate_game(ctx: Context<MigrateGame>) -> Result<()> {
+        let game_info = ctx.accounts.game.to_account_info();
+        let upgraded = legacy::read_legacy_game(&game_info.try_borrow_data()?)?;
+
+        // The game's lamports also hold the bets, so only the extra rent is topped up
+        let rent = Rent::get()?;
+        let new_len = 8 + GameState::SIZE;
+        let shortfall = rent
+            .minimum_balance(new_len)
+            .saturating_sub(rent.minimum_balance(game_info.data_len()));
+        if shortfall > 0 {
+            let transfer_instruction = system_instruction::transfer(
+                &ctx.accounts.payer.key(),
+                &game_info.key(),
+                shortfall,
+            );
+            invoke(
+                &transfer_instruction,
                 &[
-                    game_acct_info.clone(),
-                    system_program_info.clone(),
+                    ctx.accounts.payer.to_account_info().clone(),
+                    game_info.clone(),
+                    ctx.accounts.system_program.to_account_info().clone(),
                 ],
-                signer,
             )?;
         }
+        game_info.resize(new_len)?;
+
+        *ctx.accounts.game.load_mut()? = upgraded;
 
-        let game = &mut ctx.accounts.game;
-        game.game_state = GameStatus::Cancelled;
+        emit!(GameMigrated {
+            game: game_info.key(),
+            from_version: legacy::GameStateV1::VERSION,
+            to_version: GameState::VERSION,
+        });
         Ok(())
     }
 }
 
-/// Helper function to find the next active player
-fn next_active_player(game: &GameState, current_turn: u8) -> u8 {
-    let mut next_turn = (current_turn as usize + 1) % game.players.len();
+/// Token-2022 mint extensions a wager mint may carry. Fees, transfer hooks,
+/// permanent delegates and non-transferable mints would skim, block or pull the
+/// pot out of the vault, so only extensions that leave transfers alone are allowed.
+pub const ALLOWED_MINT_EXTENSIONS: [ExtensionType; 7] = [
+    ExtensionType::MintCloseAuthority,
+    ExtensionType::InterestBearingConfig,
+    ExtensionType::MetadataPointer,
+    ExtensionType::TokenMetadata,
+    ExtensionType::GroupPointer,
+    ExtensionType::TokenGroup,
+    ExtensionType::GroupMemberPointer,
+];
+
+/// Helper function to reject wager mints with an extension outside
+/// `ALLOWED_MINT_EXTENSIONS`. Legacy SPL Token mints have no extensions.
+fn check_mint_extensions(mint_data: &[u8]) -> Result<()> {
+    let mint = StateWithExtensions::<MintState>::unpack(mint_data)
+        .map_err(|_| ErrorCode::UnsupportedMintExtension)?;
+    let extensions = mint
+        .get_extension_types()
+        .map_err(|_| ErrorCode::UnsupportedMintExtension)?;
+    require!(
+        extensions
+            .iter()
+            .all(|extension| ALLOWED_MINT_EXTENSIONS.contains(extension)),
+        ErrorCode::UnsupportedMintExtension
+    );
+    Ok(())
+}
 
-    // Find the next non-default player
-    while game.players[next_turn] == Pubkey::default() {
-        next_turn = (next_turn + 1) % game.players.len();
-    }
+/// Token accounts backing a token table: the mint, the game's associated token
+/// vault and the token program (legacy SPL Token or Token-2022) that owns them.
+struct TokenVault<'a, 'info> {
+    token_program: &'a Interface<'info, TokenInterface>,
+    vault: &'a InterfaceAccount<'info, TokenAccount>,
+    mint: &'a InterfaceAccount<'info, Mint>,
+    game: AccountInfo<'info>,
+}
+
+impl<'a, 'info> TokenVault<'a, 'info> {
+    fn from_accounts(
+        token_program: &'a Option<Interface<'info, TokenInterface>>,
+        vault: &'a Option<InterfaceAccount<'info, TokenAccount>>,
+        mint: &'a Option<InterfaceAccount<'info, Mint>>,
+        game: AccountInfo<'info>,
+    ) -> Result<Self> {
+        Ok(Self {
+            token_program: token_program.as_ref().ok_or(ErrorCode::TokenAccountsMissing)?,
+            vault: vault.as_ref().ok_or(ErrorCode::TokenAccountsMissing)?,
+            mint: mint.as_ref().ok_or(ErrorCode::TokenAccountsMissing)?,
+            game,
+        })
+    }
+
+    /// Move `amount` from a player's token account into the vault
+    fn deposit(
+        &self,
+        from: &InterfaceAccount<'info, TokenAccount>,
+        authority: &Signer<'info>,
+        amount: u64,
+    ) -> Result<()> {
+        token_interface::transfer_checked(
+            CpiContext::new(
+                self.token_program.to_account_info(),
+                TransferChecked {
+                    from: from.to_account_info(),
+                    mint: self.mint.to_account_info(),
+                    to: self.vault.to_account_info(),
+                    authority: authority.to_account_info(),
+                },
+            ),
+            amount,
+            self.mint.decimals,
+        )
+    }
+
+    /// Pay `amount` out of the vault, signed by the game PDA. The destination
+    /// must be a token account of the game's mint owned by `owner`.
+    fn release(
+        &self,
+        to: &InterfaceAccount<'info, TokenAccount>,
+        owner: Pubkey,
+        amount: u64,
+        signer: &[&[&[u8]]],
+    ) -> Result<()> {
+        require_keys_eq!(to.owner, owner, ErrorCode::InvalidTokenAccount);
+        require_keys_eq!(to.mint, self.mint.key(), ErrorCode::InvalidTokenAccount);
+
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                TransferChecked {
+                    from: self.vault.to_account_info(),
+                    mint: self.mint.to_account_info(),
+                    to: to.to_account_info(),
+                    authority: self.game.clone(),
+                },
+                signer,
+            ),
+            amount,
+            self.mint.decimals,
+        )
+    }
+}
+
+/// Helper function to take a basis-point share of an amount
+fn bps_of(amount: u64, bps: u16) -> u64 {
+    (amount as u128 * bps as u128 / 10_000) as u64
+}
+
+/// Helper function to treat the default key as an empty slot
+fn optional_key(key: Pubkey) -> Option<Pubkey> {
+    (key != Pubkey::default()).then_some(key)
+}
+
+/// Helper function to resolve who is acting: the signer itself, or the player a
+/// valid session for this game delegates to the signer
+fn acting_player(
+    signer: &Signer,
+    session: &Option<Account<Session>>,
+    game_key: Pubkey,
+) -> Result<Pubkey> {
+    let Some(session) = session else {
+        return Ok(signer.key());
+    };
+    require_keys_eq!(session.game, game_key, ErrorCode::InvalidSession);
+    require_keys_eq!(session.signer, signer.key(), ErrorCode::InvalidSession);
+    require!(
+        Clock::get()?.unix_timestamp < session.expires_at,
+        ErrorCode::SessionExpired
+    );
+    Ok(session.player)
+}
+
+/// Helper function to open a game's move log for appending. Games created before
+/// history was kept have none; every other game must pass its log, with room for
+/// the `needed` entries the caller appends.
+fn open_history<'a>(
+    game: &GameState,
+    history: &'a Option<AccountLoader<GameHistory>>,
+    needed: usize,
+) -> Result<Option<HistoryLog<'a>>> {
+    if game.history == 0 {
+        return Ok(None);
+    }
+    let history = history.as_ref().ok_or(ErrorCode::HistoryMissing)?;
+    let data = AsRef::<AccountInfo>::as_ref(history).try_borrow_mut_data()?;
+    let log = HistoryLog::new(RefMut::map(data, |data| &mut **data));
+    require!(log.room() >= needed, ErrorCode::HistoryFull);
+    Ok(Some(log))
+}
+
+/// Helper function to grow a game's move log by `HISTORY_GROWTH` entries, rent
+/// paid by `payer`, once it has no room left for another turn. Called by every
+/// instruction that starts a turn or ends one early, so the oracle callback and the
+/// moves after a roll request always find room.
+fn reserve_history<'info>(
+    game: &GameState,
+    history: &Option<AccountLoader<'info, GameHistory>>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    if game.history == 0 {
+        return Ok(());
+    }
+    let history = history.as_ref().ok_or(ErrorCode::HistoryMissing)?;
+    let history_info = history.to_account_info();
+    let (count, capacity) = {
+        let data = history_info.try_borrow_data()?;
+        (GameHistory::header(&data).count as usize, GameHistory::capacity(&data))
+    };
+    if count + HISTORY_TURN_ENTRIES <= capacity {
+        return Ok(());
+    }
+
+    let new_len = GameHistory::space(count + HISTORY_GROWTH);
+    let shortfall = Rent::get()?
+        .minimum_balance(new_len)
+        .saturating_sub(history_info.lamports());
+    if shortfall > 0 {
+        let transfer_instruction =
+            system_instruction::transfer(&payer.key(), &history_info.key(), shortfall);
+        invoke(
+            &transfer_instruction,
+            &[
+                payer.to_account_info(),
+                history_info.clone(),
+                system_program.to_account_info(),
+            ],
+        )?;
+    }
+    history_info.resize(new_len)?;
+    Ok(())
+}
+
+/// Helper function to get the lobby a game is listed in. Games that never made it
+/// into a lobby have none; every other game must pass the lobby page holding it.
+fn listed_lobby<'a, 'info>(
+    game: &GameState,
+    game_key: Pubkey,
+    lobby: &'a mut Option<Account<'info, Lobby>>,
+) -> Result<Option<&'a mut Account<'info, Lobby>>> {
+    if game.listed == 0 {
+        return Ok(None);
+    }
+    let lobby = lobby.as_mut().ok_or(ErrorCode::LobbyMissing)?;
+    require!(
+        lobby.is_tier_of(game) && lobby.holds(game_key),
+        ErrorCode::InvalidLobby
+    );
+    Ok(Some(lobby))
+}
+
+/// Helper function to check a game account is in the current layout before it is
+/// loaded; accounts in older layouts must be migrated first
+fn is_current_layout(game: &AccountLoader<GameState>) -> bool {
+    match game.to_account_info().try_borrow_data() {
+        Ok(data) => is_current_data(&data),
+        Err(_) => false,
+    }
+}
+
+/// Helper function to check raw game account data is in the current layout. The
+/// size is checked first: the version byte is only meaningful in zero-copy
+/// accounts, while in the Borsh layout the same byte belongs to `creator`.
+fn is_current_data(data: &[u8]) -> bool {
+    data.len() == 8 + GameState::SIZE && data[GameState::VERSION_OFFSET] == GameState::VERSION
+}
+
+/// Helper function to pay lamports out of the game account.
+/// The System Program refuses to debit accounts that carry data, so the program
+/// debits its own account directly. The game keeps enough to stay rent-exempt.
+fn pay_from_game(game: &AccountInfo, to: &AccountInfo, amount: u64) -> Result<()> {
+    let remaining = game
+        .lamports()
+        .checked_sub(amount)
+        .ok_or(ErrorCode::InsufficientGameFunds)?;
+    require!(
+        remaining >= Rent::get()?.minimum_balance(game.data_len()),
+        ErrorCode::InsufficientGameFunds
+    );
+
+    **game.try_borrow_mut_lamports()? = remaining;
+    **to.try_borrow_mut_lamports()? += amount;
+    Ok(())
+}
+
+/// Helper function to find the next active player
+fn next_active_player(game: &GameState, current_turn: u8) -> u8 {
+    let seats = game.player_count as usize;
+    let mut next_turn = (current_turn as usize + 1) % seats;
+
+    // Find the next non-default player that still has pieces to bring home
+    while game.players[next_turn] == Pubkey::default() || !game.plays_on(next_turn) {
+        next_turn = (next_turn + 1) % seats;
+    }
 
     next_turn as u8
 }
 
-/// Helper function to check if player has any valid moves with current dice roll
-fn check_for_valid_moves(game: &GameState, player_idx: usize, dice_value: u8) -> bool {
-    // If player rolled a 6, they can always move a piece out from home yard
-    if dice_value == 6 {
-        for pos in &game.positions[player_idx] {
-            if *pos == 0 {
-                return true;
+/// Helper function to take a player out of the game, by resignation or ejection.
+///
+/// Places are filled from the top by finishing and from the bottom by elimination:
+/// once a single contender is left they take the best free place, and in games
+/// paying two places the player eliminated last takes second if it is still free.
+/// In a partnership game losing a player forfeits the game to the other team.
+fn eliminate_player(
+    game: &mut GameState,
+    game_key: Pubkey,
+    seat: usize,
+    now: i64,
+    history: Option<&mut HistoryLog>,
+) {
+    let eliminated = game.deposited_players[seat];
+    // Mark player as inactive but keep their position in the array
+    game.players[seat] = Pubkey::default();
+    if let Some(history) = history {
+        history.push(HistoryEntry::eliminated(seat as u8, now));
+    }
+
+    if game.team_mode() != TeamMode::Solo {
+        let opponent = (seat + 1) % game.player_count as usize;
+        game.winner = game.deposited_players[opponent];
+        game.second_place = game.deposited_players[game.partner_of(opponent)];
+        game.set_status(GameStatus::Completed);
+        emit!(GameCompleted {
+            game: game_key,
+            winner: game.winner(),
+            second_place: game.second_place(),
+        });
+        return;
+    }
+
+    let (last, others) = {
+        let mut contenders = game.contenders();
+        (contenders.next(), contenders.next().is_some())
+    };
+    if others {
+        return;
+    }
+
+    if let Some(last) = last {
+        if game.winner().is_none() {
+            game.winner = last;
+            if game.paid_places() > 1 {
+                game.second_place = eliminated;
             }
+        } else if game.second_place().is_none() {
+            game.second_place = last;
         }
     }
+    game.set_status(GameStatus::Completed);
 
-    // Check if any piece on the board can move
-    let home_entry = game.home_entry_positions[player_idx];
+    emit!(GameCompleted {
+        game: game_key,
+        winner: game.winner(),
+        second_place: game.second_place(),
+    });
+}
 
-    for pos in &game.positions[player_idx] {
-        if *pos > 0 && *pos < 56 {
-            // Piece is on the board but not home yet
-            let new_pos = *pos + dice_value;
+/// Helper function to verify a commit-reveal roll against the current player's
+/// hash chain, advance the chain and apply the die derived from it
+fn roll_from_reveal(
+    game: &mut GameState,
+    game_key: Pubkey,
+    reveal: [u8; 32],
+    slot_hash: [u8; 32],
+    now: i64,
+    history: Option<&mut HistoryLog>,
+) -> Result<()> {
+    // Verify the reveal against the player's hash chain and advance it
+    let seat = game.turn as usize;
+    require!(reveal != [0u8; 32], ErrorCode::RevealMissing);
+    require!(
+        hash(&reveal).to_bytes() == game.commitments[seat],
+        ErrorCode::RevealMismatch
+    );
+    game.commitments[seat] = reveal;
+
+    game.entropy = hashv(&[game.entropy.as_ref(), reveal.as_ref(), slot_hash.as_ref()]).to_bytes();
+    let random_value = dice_from_entropy(&game.entropy);
+    apply_dice_roll(game, game_key, random_value, now, history);
+    Ok(())
+}
 
-            // Check if move is valid based on home entry logic
-            if *pos <= home_entry && new_pos > home_entry {
-                let home_steps = new_pos - home_entry;
-                if home_steps <= 6 {
-                    // Valid home path move
-                    return true;
-                }
-            } else if new_pos <= 50 {
-                // Valid main track move
-                return true;
+/// Helper function to move one piece for the current player with the stored roll,
+/// then settle placings and the turn
+fn play_piece(
+    game: &mut GameState,
+    game_key: Pubkey,
+    player: Pubkey,
+    piece_index: u8,
+    now: i64,
+    history: Option<&mut HistoryLog>,
+) -> Result<MoveOutcome> {
+    let seat = game.moving_seat();
+    require!(
+        (piece_index as usize) < PIECES_PER_PLAYER,
+        ErrorCode::InvalidPiece
+    );
+
+    let dice_value = game.dice_roll().ok_or(ErrorCode::DiceNotRolled)?;
+    let mut board = game.board();
+
+    // Check if player has any valid moves
+    require!(
+        !board.legal_moves(seat, dice_value).is_empty(),
+        ErrorCode::NoValidMoves
+    );
+
+    // Validate and apply the move against the game's rule set
+    let outcome = board
+        .apply_move(seat, piece_index, dice_value)
+        .map_err(ErrorCode::from)?;
+    game.positions = board.positions;
+    if let Some(history) = history {
+        history.push(HistoryEntry::moved(seat, dice_value, &outcome, now));
+    }
+
+    emit!(PieceMoved {
+        game: game_key,
+        seat,
+        piece: piece_index,
+        from: outcome.mv.from,
+        to: outcome.mv.to,
+        dice_value,
+    });
+    for capture in outcome.captures() {
+        emit!(PieceCaptured {
+            game: game_key,
+            seat,
+            piece: piece_index,
+            captured_seat: capture.seat,
+            captured_piece: capture.piece,
+        });
+    }
+
+    if outcome.reached_home {
+        game.home_counts[seat as usize] += 1;
+        emit!(PieceHome {
+            game: game_key,
+            seat,
+            piece: piece_index,
+            home_count: game.home_counts[seat as usize],
+        });
+    }
+
+    // Check if all 4 pieces are home
+    if outcome.finished {
+        if game.team_mode() != TeamMode::Solo {
+            // A partnership wins once both partners have every piece home
+            let partner = game.partner_of(seat as usize);
+            if board.is_finished(partner as u8) {
+                game.winner = game.deposited_players[seat as usize];
+                game.second_place = game.deposited_players[partner];
+                game.set_status(GameStatus::Completed);
             }
+        } else if game.paid_places() == 1 {
+            game.winner = player;
+            game.set_status(GameStatus::Completed);
+        } else if game.winner().is_none() {
+            game.winner = player;
+            // A single contender left has second place to themselves
+            let (last, others) = {
+                let mut contenders = game.contenders();
+                (contenders.next(), contenders.next().is_some())
+            };
+            if let (Some(last), false) = (last, others) {
+                game.second_place = last;
+                game.set_status(GameStatus::Completed);
+            }
+        } else if game.second_place().is_none() && game.winner != player {
+            game.second_place = player;
+            game.set_status(GameStatus::Completed);
+        }
+
+        if game.status() == GameStatus::Completed {
+            emit!(GameCompleted {
+                game: game_key,
+                winner: game.winner(),
+                second_place: game.second_place(),
+            });
         }
     }
 
-    false // No valid moves found
+    // Update turn - player goes again on a six, capture or reaching home when the
+    // rule set grants it, unless they have just brought their last piece home
+    if !outcome.extra_turn && game.status() == GameStatus::Active {
+        game.consecutive_sixes = 0;
+        game.turn = next_active_player(game, game.turn);
+    }
+
+    game.dice_roll = 0;
+    game.last_move_time = now;
+    Ok(outcome)
+}
+
+/// Helper function to credit a played move to the mover's profile and to the
+/// profiles of the players it captured. Games created before profiles existed may
+/// leave any of them out; every other game must pass them all. A profile that is
+/// passed must be the player's own.
+fn record_move_stats<'info>(
+    game: &GameState,
+    outcome: &MoveOutcome,
+    player: Pubkey,
+    profile: &mut Option<Account<'info, PlayerProfile>>,
+    captured_profiles: &'info [AccountInfo<'info>],
+) -> Result<()> {
+    match profile {
+        Some(profile) => {
+            require_keys_eq!(profile.player, player, ErrorCode::InvalidProfile);
+            PlayerProfile::verify_address(profile)?;
+            profile.captures_made += outcome.captures().len() as u64;
+            profile.pieces_home += u64::from(outcome.reached_home);
+        }
+        None => require!(game.profiles == 0, ErrorCode::MissingProfile),
+    }
+
+    let mut credited = 0;
+    for (index, info) in captured_profiles.iter().enumerate() {
+        require!(
+            !captured_profiles[..index].iter().any(|other| other.key == info.key),
+            ErrorCode::InvalidProfile
+        );
+        let mut captured = Account::<PlayerProfile>::try_from(info)?;
+        PlayerProfile::verify_address(&captured)?;
+        let suffered = outcome
+            .captures()
+            .iter()
+            .filter(|capture| game.deposited_players[capture.seat as usize] == captured.player)
+            .count();
+        require!(suffered > 0, ErrorCode::InvalidProfile);
+        captured.captures_suffered += suffered as u64;
+        credited += suffered;
+        // Remaining accounts are not written back by Anchor
+        captured.exit(&crate::ID)?;
+    }
+    require!(
+        game.profiles == 0 || credited == outcome.captures().len(),
+        ErrorCode::MissingProfile
+    );
+    Ok(())
+}
+
+/// Helper function to record a fresh roll for the current player, applying the
+/// three-consecutive-sixes rule and passing the turn when the roll can't be played
+fn apply_dice_roll(
+    game: &mut GameState,
+    game_key: Pubkey,
+    random_value: u8,
+    now: i64,
+    mut history: Option<&mut HistoryLog>,
+) {
+    emit!(DiceRolled {
+        game: game_key,
+        seat: game.turn,
+        value: random_value,
+    });
+    if let Some(history) = history.as_deref_mut() {
+        history.push(HistoryEntry::roll(game.turn, random_value, now));
+    }
+
+    // Track consecutive sixes (standard Ludo rule)
+    if random_value == 6 {
+        game.consecutive_sixes += 1;
+        if game.rules().three_sixes_forfeit && game.consecutive_sixes >= 3 {
+            emit!(TurnSkipped {
+                game: game_key,
+                seat: game.turn,
+                reason: TurnSkipReason::ThreeSixes,
+            });
+            if let Some(history) = history.as_deref_mut() {
+                history.push(HistoryEntry::skip(game.turn, TurnSkipReason::ThreeSixes, now));
+            }
+            game.consecutive_sixes = 0;
+            game.dice_roll = 0;
+            game.turn = next_active_player(game, game.turn);
+            game.last_move_time = now;
+            return;
+        }
+    } else {
+        game.consecutive_sixes = 0;
+    }
+
+    // Pass the turn straight away instead of waiting for the player to time out
+    if game.board().legal_moves(game.moving_seat(), random_value).is_empty() {
+        emit!(TurnSkipped {
+            game: game_key,
+            seat: game.turn,
+            reason: TurnSkipReason::NoLegalMoves,
+        });
+        if let Some(history) = history {
+            history.push(HistoryEntry::skip(game.turn, TurnSkipReason::NoLegalMoves, now));
+        }
+        game.consecutive_sixes = 0;
+        game.dice_roll = 0;
+        game.turn = next_active_player(game, game.turn);
+        game.last_move_time = now;
+        return;
+    }
+
+    game.dice_roll = random_value;
+    game.last_move_time = now;
+}
+
+/// Helper function to consume the game's pending roll request and return the
+/// hash of the slot it was made in
+fn requested_slot_hash(game: &mut GameState, slot_hashes: &AccountInfo) -> Result<[u8; 32]> {
+    let request_slot = take_roll_request(game)?;
+    slot_hash_from(&slot_hashes.try_borrow_data()?, request_slot)
+}
+
+/// Helper function to consume the game's pending roll request, which can only be
+/// answered after the slot it was made in and before it lapses. Returns that slot.
+fn take_roll_request(game: &mut GameState) -> Result<u64> {
+    require!(
+        game.randomness_requested != 0,
+        ErrorCode::RandomnessNotRequested
+    );
+    let current_slot = Clock::get()?.slot;
+    require!(
+        current_slot > game.randomness_request_slot,
+        ErrorCode::RandomnessNotReady
+    );
+    require!(
+        current_slot - game.randomness_request_slot <= ROLL_REQUEST_WINDOW_SLOTS,
+        ErrorCode::RollRequestExpired
+    );
+
+    game.randomness_requested = 0;
+    Ok(game.randomness_request_slot)
+}
+
+/// Helper function to request the roll of the seat holding the turn as soon as the
+/// turn is waiting on one, so a commit-reveal roller can reveal in any later slot and
+/// move in the same transaction. Oracle games and seats without a hash chain yet
+/// still ask through `request_roll`.
+fn request_next_roll(game: &mut GameState, slot: u64) {
+    if game.status() == GameStatus::Active
+        && game.dice_roll().is_none()
+        && game.randomness_requested == 0
+        && game.oracle().is_none()
+        && game.commitments[game.turn as usize] != [0u8; 32]
+    {
+        game.randomness_requested = 1;
+        game.randomness_request_slot = slot;
+    }
+}
+
+/// Helper function to find the hash of the first slot at or after `slot` in the
+/// SlotHashes sysvar. The sysvar is too large to deserialize, so it is scanned in
+/// place: a u64 entry count followed by `(slot: u64, hash: [u8; 32])` pairs, newest
+/// first. Skipped slots have no entry, so the next produced slot stands in for them.
+#[allow(unknown_lints, clippy::chunks_exact_to_as_chunks)] // as_chunks needs Rust 1.88
+fn slot_hash_from(data: &[u8], slot: u64) -> Result<[u8; 32]> {
+    const ENTRY_LEN: usize = 8 + 32;
+    require!(data.len() >= 8, ErrorCode::InsufficientRandomness);
+    let count = u64::from_le_bytes(data[..8].try_into().unwrap()) as usize;
+
+    let mut found = None;
+    let mut reached_older = false;
+    for entry in data[8..].chunks_exact(ENTRY_LEN).take(count) {
+        let entry_slot = u64::from_le_bytes(entry[..8].try_into().unwrap());
+        if entry_slot < slot {
+            reached_older = true;
+            break;
+        }
+        found = Some((entry_slot, &entry[8..]));
+    }
+
+    match found {
+        // Without an older entry the stand-in could have dropped off the end
+        Some((entry_slot, _)) if !reached_older && entry_slot != slot => {
+            err!(ErrorCode::RollRequestExpired)
+        }
+        Some((_, hash)) => Ok(hash.try_into().unwrap()),
+        None => err!(ErrorCode::RandomnessNotReady),
+    }
+}
+
+/// Helper function to map 32 bytes of entropy onto a die face (1..=6)
+fn dice_from_entropy(entropy: &[u8; 32]) -> u8 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&entropy[..8]);
+    (u64::from_le_bytes(bytes) % 6) as u8 + 1
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Config::INIT_SPACE,
+        seeds = [b"config".as_ref()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::LudoGame>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @ ErrorCode::InvalidAdmin)]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::InvalidAdmin,
+        realloc = 8 + Config::INIT_SPACE,
+        realloc::payer = admin,
+        realloc::zero = false
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(max_players: u8, bet_amount: u64, game_seed: String)]
+#[instruction(max_players: u8, bet_amount: u64, lobby_page: u32)]
 pub struct InitializeGame<'info> {
+    #[account(
+        init_if_needed,
+        payer = creator,
+        space = 8 + GameCounter::INIT_SPACE,
+        seeds = [b"game_counter".as_ref()],
+        bump
+    )]
+    pub game_counter: Account<'info, GameCounter>,
     #[account(
         init,
         payer = creator,
-        space = 8 + GameState::INIT_SPACE,
-        seeds = [b"game".as_ref(), game_seed.as_bytes()],
+        space = 8 + GameState::SIZE,
+        seeds = [b"game".as_ref(), game_counter.next_id.to_le_bytes().as_ref()],
         bump
     )]
-    pub game: Account<'info, GameState>,
+    pub game: AccountLoader<'info, GameState>,
+    #[account(
+        init,
+        payer = creator,
+        space = GameHistory::space(HISTORY_INITIAL_CAPACITY),
+        seeds = [b"history".as_ref(), game.key().as_ref()],
+        bump
+    )]
+    pub history: AccountLoader<'info, GameHistory>,
+    #[account(
+        init_if_needed,
+        payer = creator,
+        space = 8 + PlayerProfile::INIT_SPACE,
+        seeds = [b"profile".as_ref(), creator.key().as_ref()],
+        bump
+    )]
+    pub profile: Account<'info, PlayerProfile>,
+    #[account(seeds = [b"config".as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
     #[account(mut)]
     pub creator: Signer<'info>,
+    /// Wager mint for token tables, omitted for SOL tables
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init_if_needed,
+        payer = creator,
+        space = 8 + Lobby::INIT_SPACE,
+        seeds = [
+            b"lobby".as_ref(),
+            mint.as_ref().map(|mint| mint.key()).unwrap_or_default().as_ref(),
+            bet_amount.to_le_bytes().as_ref(),
+            &[max_players],
+            lobby_page.to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pub lobby: Account<'info, Lobby>,
+    #[account(
+        init,
+        payer = creator,
+        associated_token::mint = mint,
+        associated_token::authority = game,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = creator,
+        token::token_program = token_program,
+    )]
+    pub creator_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
 pub struct JoinGame<'info> {
-    #[account(mut)]
-    pub game: Account<'info, GameState>,
+    #[account(mut, constraint = is_current_layout(&game) @ ErrorCode::GameNeedsMigration)]
+    pub game: AccountLoader<'info, GameState>,
     #[account(mut)]
     pub player: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + PlayerProfile::INIT_SPACE,
+        seeds = [b"profile".as_ref(), player.key().as_ref()],
+        bump
+    )]
+    pub profile: Account<'info, PlayerProfile>,
+    /// Lobby the game is listed in, omitted only for games that were never listed
+    #[account(mut)]
+    pub lobby: Option<Account<'info, Lobby>>,
+    #[account(constraint = game.load()?.mint() == Some(mint.key()) @ ErrorCode::InvalidMint)]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = game,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = player,
+        token::token_program = token_program,
+    )]
+    pub player_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Option<Interface<'info, TokenInterface>>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 pub struct SimpleRandomness<'info> {
+    #[account(mut, constraint = is_current_layout(&game) @ ErrorCode::GameNeedsMigration)]
+    pub game: AccountLoader<'info, GameState>,
+    /// The player's wallet, or a session key when `session` is passed. Pays the rent
+    /// when the move log has to grow.
+    #[account(mut)]
+    pub player: Signer<'info>,
+    pub session: Option<Account<'info, Session>>,
+    /// Move log, omitted only for games created before history was kept
+    #[account(mut, seeds = [b"history".as_ref(), game.key().as_ref()], bump)]
+    pub history: Option<AccountLoader<'info, GameHistory>>,
+    /// The acting player's profile, credited with the move if `roll_and_move` plays
+    /// one. Omitted only for games created before profiles existed.
+    #[account(mut)]
+    pub profile: Option<Account<'info, PlayerProfile>>,
+    /// CHECK: SlotHashes sysvar, read manually in `slot_hash_from`
+    #[account(address = sysvar::slot_hashes::ID)]
+    pub recent_slothashes: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RequestRoll<'info> {
+    #[account(mut, constraint = is_current_layout(&game) @ ErrorCode::GameNeedsMigration)]
+    pub game: AccountLoader<'info, GameState>,
+    /// The player's wallet, or a session key when `session` is passed. Pays the rent
+    /// when the move log has to grow.
+    #[account(mut)]
+    pub player: Signer<'info>,
+    pub session: Option<Account<'info, Session>>,
+    /// Move log, omitted only for games created before history was kept
+    #[account(mut, seeds = [b"history".as_ref(), game.key().as_ref()], bump)]
+    pub history: Option<AccountLoader<'info, GameHistory>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FulfillRandomness<'info> {
+    #[account(mut, constraint = is_current_layout(&game) @ ErrorCode::GameNeedsMigration)]
+    pub game: AccountLoader<'info, GameState>,
+    /// Oracle authority registered on the game. For Switchboard/ORAO-style oracles
+    /// this is the PDA their program signs the callback with; in tests it is the
+    /// mock oracle's keypair.
+    pub oracle_authority: Signer<'info>,
+    /// Move log, omitted only for games created before history was kept
+    #[account(mut, seeds = [b"history".as_ref(), game.key().as_ref()], bump)]
+    pub history: Option<AccountLoader<'info, GameHistory>>,
+}
+
+#[derive(Accounts)]
+pub struct RotateCommitment<'info> {
+    #[account(mut, constraint = is_current_layout(&game) @ ErrorCode::GameNeedsMigration)]
+    pub game: AccountLoader<'info, GameState>,
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimTimeout<'info> {
+    #[account(mut, constraint = is_current_layout(&game) @ ErrorCode::GameNeedsMigration)]
+    pub game: AccountLoader<'info, GameState>,
+    /// Pays the rent when the move log has to grow
     #[account(mut)]
-    pub game: Account<'info, GameState>,
+    pub caller: Signer<'info>,
+    /// Move log, omitted only for games created before history was kept
+    #[account(mut, seeds = [b"history".as_ref(), game.key().as_ref()], bump)]
+    pub history: Option<AccountLoader<'info, GameHistory>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Resign<'info> {
+    #[account(mut, constraint = is_current_layout(&game) @ ErrorCode::GameNeedsMigration)]
+    pub game: AccountLoader<'info, GameState>,
+    /// Pays the rent when the move log has to grow
     #[account(mut)]
     pub player: Signer<'info>,
+    /// Move log, omitted only for games created before history was kept
+    #[account(mut, seeds = [b"history".as_ref(), game.key().as_ref()], bump)]
+    pub history: Option<AccountLoader<'info, GameHistory>>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 pub struct MovePiece<'info> {
+    #[account(mut, constraint = is_current_layout(&game) @ ErrorCode::GameNeedsMigration)]
+    pub game: AccountLoader<'info, GameState>,
+    /// The player's wallet, or a session key when `session` is passed
     #[account(mut)]
-    pub game: Account<'info, GameState>,
+    pub player: Signer<'info>,
+    pub session: Option<Account<'info, Session>>,
+    /// Move log, omitted only for games created before history was kept
+    #[account(mut, seeds = [b"history".as_ref(), game.key().as_ref()], bump)]
+    pub history: Option<AccountLoader<'info, GameHistory>>,
+    /// The acting player's profile, omitted only for games created before profiles
+    /// existed. Checked against the player in `move_piece`.
+    #[account(mut)]
+    pub profile: Option<Account<'info, PlayerProfile>>,
+}
+
+#[derive(Accounts)]
+pub struct CreateSession<'info> {
+    #[account(
+        init,
+        payer = player,
+        space = 8 + Session::INIT_SPACE,
+        seeds = [b"session".as_ref(), game.key().as_ref(), player.key().as_ref()],
+        bump
+    )]
+    pub session: Account<'info, Session>,
+    #[account(constraint = is_current_layout(&game) @ ErrorCode::GameNeedsMigration)]
+    pub game: AccountLoader<'info, GameState>,
+    #[account(mut)]
+    pub player: Signer<'info>,
+    /// CHECK: Ephemeral keypair being authorized, only receives lamports
+    #[account(mut)]
+    pub session_signer: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeSession<'info> {
+    #[account(
+        mut,
+        close = player,
+        seeds = [b"session".as_ref(), session.game.as_ref(), player.key().as_ref()],
+        bump = session.bump,
+        has_one = player @ ErrorCode::InvalidSession
+    )]
+    pub session: Account<'info, Session>,
     #[account(mut)]
     pub player: Signer<'info>,
 }
 
 #[derive(Accounts)]
 pub struct DistributePrizes<'info> {
+    #[account(mut, constraint = is_current_layout(&game) @ ErrorCode::GameNeedsMigration)]
+    pub game: AccountLoader<'info, GameState>,
+    #[account(seeds = [b"config".as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    /// CHECK: This is the platform fee recipient, must be the config treasury
+    #[account(mut, address = config.treasury @ ErrorCode::InvalidTreasury)]
+    pub platform: UncheckedAccount<'info>,
+    /// CHECK: Only receives lamports, must be the wallet recorded in `game.winner`
+    #[account(
+        mut,
+        constraint = game.load()?.winner() == Some(winner.key()) @ ErrorCode::WinnerAccountMismatch
+    )]
+    pub winner: UncheckedAccount<'info>,
+    /// CHECK: Only receives lamports, must be the wallet recorded in `game.second_place`.
+    /// Omitted in games that pay out a single winner.
+    #[account(
+        mut,
+        constraint = game.load()?.second_place() == Some(second_place.key()) @ ErrorCode::SecondPlaceAccountMismatch
+    )]
+    pub second_place: Option<UncheckedAccount<'info>>,
+    #[account(constraint = game.load()?.mint() == Some(mint.key()) @ ErrorCode::InvalidMint)]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = game,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(mut)]
-    pub game: Account<'info, GameState>,
-    /// CHECK: This is the platform fee recipient
+    pub platform_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(mut)]
-    pub platform: UncheckedAccount<'info>,
+    pub winner_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub second_place_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Option<Interface<'info, TokenInterface>>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 pub struct CancelGame<'info> {
-    #[account(mut)]
-    pub game: Account<'info, GameState>,
+    #[account(mut, constraint = is_current_layout(&game) @ ErrorCode::GameNeedsMigration)]
+    pub game: AccountLoader<'info, GameState>,
     #[account(mut)]
     pub creator: Signer<'info>,
+    /// Lobby the game is listed in, omitted only for games that were never listed
+    #[account(mut)]
+    pub lobby: Option<Account<'info, Lobby>>,
+    #[account(constraint = game.load()?.mint() == Some(mint.key()) @ ErrorCode::InvalidMint)]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = game,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Option<Interface<'info, TokenInterface>>,
     pub system_program: Program<'info, System>,
 }
 
-#[account]
+#[derive(Accounts)]
+pub struct MigrateGame<'info> {
+    #[account(mut)]
+    pub game: AccountLoader<'info, GameState>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Source of verifiable randomness that can fulfill a pending roll request.
+///
+/// Implementations authenticate the fulfillment against the game's configured
+/// oracle and hand back the 32 bytes of randomness to turn into a die face.
+pub trait RandomnessOracle {
+    fn consume(&self, game: &GameState) -> Result<[u8; 32]>;
+}
+
+/// Oracle that delivers randomness through a callback signed by the game's
+/// oracle authority. Covers VRF programs that sign their callback CPI with a
+/// PDA as well as a local mock oracle signing with a keypair.
+pub struct SignedCallbackOracle<'a, 'info> {
+    pub authority: &'a Signer<'info>,
+    pub randomness: [u8; 32],
+}
+
+impl<'a, 'info> RandomnessOracle for SignedCallbackOracle<'a, 'info> {
+    fn consume(&self, game: &GameState) -> Result<[u8; 32]> {
+        require!(
+            game.oracle() == Some(self.authority.key()),
+            ErrorCode::InvalidOracle
+        );
+        require!(
+            self.randomness != [0u8; 32],
+            ErrorCode::InsufficientRandomness
+        );
+        Ok(self.randomness)
+    }
+}
+
+/// House rules for a table, fixed when the game is created
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct RuleSet {
+    pub leave_base_on_one: bool,     // A 1 also moves a piece out of base
+    pub extra_turn_on_six: bool,
+    pub extra_turn_on_capture: bool,
+    pub extra_turn_on_home: bool,    // Bringing a piece home grants another roll
+    pub exact_roll_to_finish: bool,  // Overshooting home is invalid instead of finishing
+    pub safe_start_squares: bool,    // No captures on any seat's start square
+    pub three_sixes_forfeit: bool,
+    pub blockades: bool, // Two pieces of a seat on one square can't be passed or landed on
+}
+
+impl RuleSet {
+    /// Classic rules: six to leave base, extra turn on six, three sixes forfeit, blockades
+    pub const STANDARD: RuleSet = RuleSet {
+        leave_base_on_one: false,
+        extra_turn_on_six: true,
+        extra_turn_on_capture: false,
+        extra_turn_on_home: false,
+        exact_roll_to_finish: true,
+        safe_start_squares: false,
+        three_sixes_forfeit: true,
+        blockades: true,
+    };
+
+    /// Pack the rules into the bit flags stored on `GameState`
+    pub fn to_flags(self) -> u8 {
+        self.leave_base_on_one as u8
+            | (self.extra_turn_on_six as u8) << 1
+            | (self.extra_turn_on_capture as u8) << 2
+            | (self.extra_turn_on_home as u8) << 3
+            | (self.exact_roll_to_finish as u8) << 4
+            | (self.safe_start_squares as u8) << 5
+            | (self.three_sixes_forfeit as u8) << 6
+            | (self.blockades as u8) << 7
+    }
+
+    pub fn from_flags(flags: u8) -> Self {
+        RuleSet {
+            leave_base_on_one: flags & 1 != 0,
+            extra_turn_on_six: flags & (1 << 1) != 0,
+            extra_turn_on_capture: flags & (1 << 2) != 0,
+            extra_turn_on_home: flags & (1 << 3) != 0,
+            exact_roll_to_finish: flags & (1 << 4) != 0,
+            safe_start_squares: flags & (1 << 5) != 0,
+            three_sixes_forfeit: flags & (1 << 6) != 0,
+            blockades: flags & (1 << 7) != 0,
+        }
+    }
+}
+
+impl From<RuleSet> for rules::Rules {
+    fn from(rules: RuleSet) -> Self {
+        rules::Rules {
+            leave_base_on_one: rules.leave_base_on_one,
+            extra_turn_on_six: rules.extra_turn_on_six,
+            extra_turn_on_capture: rules.extra_turn_on_capture,
+            extra_turn_on_home: rules.extra_turn_on_home,
+            exact_roll_to_finish: rules.exact_roll_to_finish,
+            safe_start_squares: rules.safe_start_squares,
+            blockades: rules.blockades,
+        }
+    }
+}
+
+/// Partnership setting for a table, fixed when the game is created
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TeamMode {
+    Solo,
+    Partners,       // Opposite seats team up and win together once both finish
+    PartnersPlayOn, // As Partners, and a finished player moves their partner's pieces
+}
+
+impl TeamMode {
+    /// Decode the team mode byte stored on `GameState`
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => TeamMode::Partners,
+            2 => TeamMode::PartnersPlayOn,
+            _ => TeamMode::Solo,
+        }
+    }
+}
+
+/// Fixed-layout game account, read in place without (de)serialization.
+///
+/// Per-seat data lives in arrays sized by `MAX_SEATS`; only the first
+/// `player_count` entries are occupied. Optional keys use the default key for
+/// "none" and `dice_roll` uses 0. Fields are grouped by alignment so the layout
+/// has no implicit padding.
+#[account(zero_copy)]
 pub struct GameState {
-    pub creator: Pubkey,
-    pub players: Vec<Pubkey>,
-    pub deposited_players: Vec<Pubkey>,
+    pub version: u8,      // Layout version, see `GameState::VERSION`
+    pub player_count: u8, // Occupied seats
     pub max_players: u8,
+    pub track_squares: u8, // Squares on the shared track
+    pub turn: u8,
+    pub game_state: u8,        // GameStatus
+    pub dice_roll: u8,         // 0 until rolled
+    pub consecutive_sixes: u8, // Track consecutive sixes for standard Ludo rule
     pub bet_amount: u64,
     pub total_bet: u64,
     pub platform_fee: u64,
     pub prize_pool: u64,
-    pub track_length: u8,
-    pub positions: Vec<Vec<u8>>,
-    pub home_counts: Vec<u8>, // Track how many pieces each player has at home
-    pub start_offsets: Vec<u8>,
-    pub home_entry_positions: Vec<u8>, // Position right before home path entry
-    pub safe_zones: Vec<u8>,
-    pub turn: u8,
     pub last_move_time: i64,
-    pub winner: Option<Pubkey>,
-    pub second_place: Option<Pubkey>,
-    pub game_state: GameStatus,
-    pub dice_roll: Option<u8>,
-    pub consecutive_sixes: u8, // Track consecutive sixes for standard Ludo rule
-    pub missed_turns: Vec<u8>,
-    pub randomness_requested: bool,
-    pub seed: String,
+    pub turn_timeout: i64,            // Seconds before the current turn can be claimed
+    pub randomness_request_slot: u64, // Slot the pending roll request was made in
+    pub creator: Pubkey,
+    pub players: [Pubkey; MAX_SEATS], // Default key once a player is removed
+    pub deposited_players: [Pubkey; MAX_SEATS],
+    pub winner: Pubkey,
+    pub second_place: Pubkey,
+    pub oracle: Pubkey, // VRF oracle authority, default for commit-reveal games
+    pub mint: Pubkey,   // Wager mint for token tables, default for SOL tables
+    pub commitments: [[u8; 32]; MAX_SEATS], // Current hash-chain head for each seat
+    pub entropy: [u8; 32],                  // Running mix of every reveal and slot hash
+    pub seed: [u8; MAX_SEED_LEN],
+    pub fee_bps: u16, // Platform fee copied from the config at creation
+    pub randomness_requested: u8,
+    pub max_missed_turns: u8, // Missed turns before a player is ejected
+    pub rules: u8,            // RuleSet flags
+    pub safe_zone_count: u8,
+    pub seed_len: u8,
     pub bump: u8,
+    pub track_length: u8, // Position of a finished piece
+    pub positions: [[u8; PIECES_PER_PLAYER]; MAX_SEATS],
+    pub home_counts: [u8; MAX_SEATS], // Track how many pieces each player has at home
+    pub start_offsets: [u8; MAX_SEATS],
+    pub missed_turns: [u8; MAX_SEATS],
+    pub safe_zones: [u8; MAX_SAFE_SQUARES],
+    pub team_mode: u8, // TeamMode
+    pub history: u8,   // 1 when the game keeps a GameHistory log
+    pub listed: u8,    // 1 while the game is listed in its Lobby
+    pub profiles: u8,  // 1 when every seat has a PlayerProfile that must be credited
+    pub _padding: [u8; 5],
 }
 
 impl GameState {
-    pub const INIT_SPACE: usize = 32 +                                        // creator
-        (32 * 10) +                                 // players (vec with max 10 elements)
-        (32 * 10) +                                 // deposited_players (vec with max 10 elements)
-        1 +                                         // max_players
+    pub const SIZE: usize = std::mem::size_of::<GameState>();
+    /// Layout version written by `initialize_game` and `migrate_game`
+    pub const VERSION: u8 = 2;
+    /// Offset of `version` in the account data. It leads the zero-copy layout and
+    /// new fields are only ever appended, so the offset holds from here on; the
+    /// Borsh layout before it is recognised by its size instead.
+    pub const VERSION_OFFSET: usize = 8 + std::mem::offset_of!(GameState, version);
+
+    pub fn status(&self) -> GameStatus {
+        GameStatus::from_u8(self.game_state)
+    }
+
+    pub fn set_status(&mut self, status: GameStatus) {
+        self.game_state = status as u8;
+    }
+
+    /// Seats taken so far, including players that have since been removed
+    pub fn seats(&self) -> &[Pubkey] {
+        &self.players[..self.player_count as usize]
+    }
+
+    /// Players still competing for a place: neither out of the game nor placed
+    pub fn contenders(&self) -> impl Iterator<Item = Pubkey> + '_ {
+        self.seats().iter().copied().filter(|p| {
+            *p != Pubkey::default() && *p != self.winner && *p != self.second_place
+        })
+    }
+
+    pub fn seat_of(&self, player: &Pubkey) -> Option<usize> {
+        self.seats().iter().position(|p| p == player)
+    }
+
+    pub fn winner(&self) -> Option<Pubkey> {
+        optional_key(self.winner)
+    }
+
+    pub fn second_place(&self) -> Option<Pubkey> {
+        optional_key(self.second_place)
+    }
+
+    pub fn oracle(&self) -> Option<Pubkey> {
+        optional_key(self.oracle)
+    }
+
+    pub fn mint(&self) -> Option<Pubkey> {
+        optional_key(self.mint)
+    }
+
+    pub fn dice_roll(&self) -> Option<u8> {
+        (self.dice_roll != 0).then_some(self.dice_roll)
+    }
+
+    pub fn rules(&self) -> RuleSet {
+        RuleSet::from_flags(self.rules)
+    }
+
+    pub fn team_mode(&self) -> TeamMode {
+        TeamMode::from_u8(self.team_mode)
+    }
+
+    /// Seat sitting opposite `seat`, its partner in a partnership game
+    pub fn partner_of(&self, seat: usize) -> usize {
+        let seats = self.player_count as usize;
+        (seat + seats / 2) % seats
+    }
+
+    /// Seat whose pieces the current player moves: their own, or their partner's
+    /// once their own are all home and finished partners play on
+    pub fn moving_seat(&self) -> u8 {
+        let turn = self.turn as usize;
+        if self.team_mode() == TeamMode::PartnersPlayOn
+            && self.home_counts[turn] as usize == PIECES_PER_PLAYER
+        {
+            return self.partner_of(turn) as u8;
+        }
+        self.turn
+    }
+
+    /// Seat still has pieces to move, its own or, when finished partners play on,
+    /// its partner's
+    pub fn plays_on(&self, seat: usize) -> bool {
+        let unfinished = |s: usize| (self.home_counts[s] as usize) < PIECES_PER_PLAYER;
+        unfinished(seat)
+            || (self.team_mode() == TeamMode::PartnersPlayOn && unfinished(self.partner_of(seat)))
+    }
+
+    /// Finishing places that share the pot at this table size
+    pub fn paid_places(&self) -> u8 {
+        BoardLayout::for_seats(self.max_players).map_or(1, |layout| layout.paid_places)
+    }
+
+    /// Finishing rank of every seat for rating, lower is better: the winner, then
+    /// second place, then everyone else tied. Winning partners share first place.
+    pub fn finishing_ranks(&self) -> [u8; MAX_SEATS] {
+        let mut ranks = [2; MAX_SEATS];
+        for (seat, player) in self.deposited_players.iter().enumerate() {
+            if Some(*player) == self.winner() {
+                ranks[seat] = 0;
+            } else if Some(*player) == self.second_place() {
+                ranks[seat] = if self.team_mode() == TeamMode::Solo { 1 } else { 0 };
+            }
+        }
+        ranks
+    }
+
+    /// Copy the pieces and board geometry into the rules engine
+    pub fn board(&self) -> Board {
+        let mut active = [false; MAX_SEATS];
+        for (seat, player) in self.seats().iter().enumerate() {
+            active[seat] = *player != Pubkey::default();
+        }
+
+        Board {
+            seats: self.player_count,
+            positions: self.positions,
+            active,
+            track_squares: self.track_squares,
+            start_offsets: self.start_offsets,
+            safe_squares: self.safe_zones,
+            safe_count: self.safe_zone_count,
+            rules: self.rules().into(),
+            teams: self.team_mode() != TeamMode::Solo,
+        }
+    }
+}
+
+#[cfg(test)]
+impl GameState {
+    /// A game under the standard rules with every seat taken by `players`, each of
+    /// whom paid `bet_amount`, with a 10% fee, a minute per turn and ejection after
+    /// three missed turns
+    pub(crate) fn seated(players: &[Pubkey], bet_amount: u64, status: GameStatus) -> Self {
+        let seats = players.len();
+        let layout = BoardLayout::for_seats(seats as u8).unwrap();
+        let mut game = <GameState as bytemuck::Zeroable>::zeroed();
+        game.version = GameState::VERSION;
+        game.player_count = seats as u8;
+        game.max_players = seats as u8;
+        game.players[..seats].copy_from_slice(players);
+        game.deposited_players[..seats].copy_from_slice(players);
+        game.creator = players[0];
+        game.bet_amount = bet_amount;
+        game.total_bet = bet_amount * seats as u64;
+        game.fee_bps = 1_000;
+        game.turn_timeout = 60;
+        game.max_missed_turns = 3;
+        game.rules = RuleSet::STANDARD.to_flags();
+        game.track_squares = layout.track_squares;
+        game.track_length = layout.finish();
+        game.start_offsets = layout.start_offsets;
+        game.safe_zones = layout.safe_squares;
+        game.safe_zone_count = layout.safe_count;
+        game.set_status(status);
+        game
+    }
+}
+
+#[account]
+pub struct Config {
+    pub admin: Pubkey,
+    pub treasury: Pubkey, // Receives platform fees
+    pub fee_bps: u16,
+    pub min_bet: u64,
+    pub max_bet: u64,
+    pub allowed_player_counts: Vec<u8>,
+    pub turn_timeout: i64,
+    pub max_missed_turns: u8, // Missed turns before a player is ejected
+    pub oracles: Vec<Pubkey>, // VRF oracle authorities games may be created with
+    pub bump: u8,
+}
+
+impl Config {
+    pub const INIT_SPACE: usize = 32 +                                        // admin
+        32 +                                        // treasury
+        2 +                                         // fee_bps
+        8 +                                         // min_bet
+        8 +                                         // max_bet
+        (4 + SUPPORTED_PLAYER_COUNTS.len()) +       // allowed_player_counts
+        8 +                                         // turn_timeout
+        1 +                                         // max_missed_turns
+        (4 + 32 * MAX_ORACLES) +                    // oracles
+        1; // bump
+
+    /// Validate and store admin-supplied settings
+    fn apply(&mut self, params: ConfigParams) -> Result<()> {
+        require!(params.fee_bps <= MAX_FEE_BPS, ErrorCode::InvalidFee);
+        require!(
+            params.min_bet > 0 && params.min_bet <= params.max_bet,
+            ErrorCode::InvalidBetAmount
+        );
+        require!(
+            !params.allowed_player_counts.is_empty()
+                && params.allowed_player_counts.len() <= SUPPORTED_PLAYER_COUNTS.len()
+                && params
+                    .allowed_player_counts
+                    .iter()
+                    .all(|count| SUPPORTED_PLAYER_COUNTS.contains(count)),
+            ErrorCode::InvalidPlayerCount
+        );
+        require!(
+            params.turn_timeout > 0 && params.max_missed_turns > 0,
+            ErrorCode::InvalidTimeout
+        );
+        require!(
+            params.oracles.len() <= MAX_ORACLES && !params.oracles.contains(&Pubkey::default()),
+            ErrorCode::InvalidOracleList
+        );
+
+        self.treasury = params.treasury;
+        self.fee_bps = params.fee_bps;
+        self.min_bet = params.min_bet;
+        self.max_bet = params.max_bet;
+        self.allowed_player_counts = params.allowed_player_counts;
+        self.turn_timeout = params.turn_timeout;
+        self.max_missed_turns = params.max_missed_turns;
+        self.oracles = params.oracles;
+        Ok(())
+    }
+}
+
+/// Hands out game ids, seeded by `[b"game_counter"]`. Created with the first game.
+#[account]
+pub struct GameCounter {
+    pub next_id: u64, // Every id below this has a game at `GameCounter::game_address`
+    pub bump: u8,
+}
+
+impl GameCounter {
+    pub const INIT_SPACE: usize = 8 +                                         // next_id
+        1; // bump
+
+    /// Address of the game created with `game_id`
+    pub fn game_address(game_id: u64) -> Pubkey {
+        Pubkey::find_program_address(&[b"game".as_ref(), &game_id.to_le_bytes()], &crate::ID).0
+    }
+}
+
+/// One page of the tables of a tier waiting for players, seeded by `[b"lobby", mint,
+/// bet_amount, max_players, page]` with the default key as mint for SOL tables.
+/// Pages are numbered from 0 and created as earlier ones fill up, so clients read
+/// pages in order until one does not exist.
+#[account]
+pub struct Lobby {
+    pub mint: Pubkey,
+    pub bet_amount: u64,
+    pub max_players: u8,
+    pub page: u32,
+    pub bump: u8,
+    pub games: Vec<LobbyEntry>,
+}
+
+/// One open table in a lobby, with what clients need to filter on
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct LobbyEntry {
+    pub game: Pubkey,
+    pub player_count: u8,
+    pub rules: u8,     // RuleSet flags
+    pub team_mode: u8, // TeamMode
+}
+
+impl LobbyEntry {
+    pub const INIT_SPACE: usize = 32 + 1 + 1 + 1;
+}
+
+impl Lobby {
+    pub const INIT_SPACE: usize = 32 +                                        // mint
         8 +                                         // bet_amount
-        8 +                                         // total_bet
-        8 +                                         // platform_fee
-        8 +                                         // prize_pool
-        1 +                                         // track_length
-        (10 * (4 * 1)) +                            // positions (vec of vec with max 10 players * 4 pieces)
-        (10 * 1) +                                  // home_counts (vec with max 10 players)
-        (4 * 1) +                                   // start_offsets (vec with max 4 elements)
-        (4 * 1) +                                   // home_entry_positions (vec with max 4 elements)
-        (8 * 1) +                                   // safe_zones (vec with max 8 elements)
-        1 +                                         // turn
-        8 +                                         // last_move_time
-        (1 + 32) +                                  // winner (Option<Pubkey>)
-        (1 + 32) +                                  // second_place (Option<Pubkey>)
-        1 +                                         // game_state
-        (1 + 1) +                                   // dice_roll (Option<u8>)
-        1 +                                         // consecutive_sixes
-        (10 * 1) +                                  // missed_turns (vec with max 10 elements)
-        1 +                                         // randomness_requested
-        (4 + 32) +                                  // seed (String with max 32 chars)
+        1 +                                         // max_players
+        4 +                                         // page
         1 +                                         // bump
-        200; // padding for future use
+        (4 + LOBBY_CAPACITY * LobbyEntry::INIT_SPACE); // games
+
+    /// Address of lobby page `page` for tables of `max_players` betting `bet_amount`
+    /// of `mint`, or lamports when `mint` is `None`
+    pub fn address(mint: Option<Pubkey>, bet_amount: u64, max_players: u8, page: u32) -> Pubkey {
+        let mint = mint.unwrap_or_default();
+        Pubkey::find_program_address(
+            &[
+                b"lobby".as_ref(),
+                mint.as_ref(),
+                &bet_amount.to_le_bytes(),
+                &[max_players],
+                &page.to_le_bytes(),
+            ],
+            &crate::ID,
+        )
+        .0
+    }
+
+    /// Open tables playing `rules` in `team_mode`
+    pub fn games_with(
+        &self,
+        rules: RuleSet,
+        team_mode: TeamMode,
+    ) -> impl Iterator<Item = &LobbyEntry> + '_ {
+        self.games.iter().filter(move |entry| {
+            entry.rules == rules.to_flags() && entry.team_mode == team_mode as u8
+        })
+    }
+
+    fn is_tier_of(&self, game: &GameState) -> bool {
+        self.mint == game.mint
+            && self.bet_amount == game.bet_amount
+            && self.max_players == game.max_players
+    }
+
+    fn holds(&self, game: Pubkey) -> bool {
+        self.games.iter().any(|entry| entry.game == game)
+    }
+
+    /// Add a table, returning false if the page is full
+    fn list(&mut self, entry: LobbyEntry) -> bool {
+        if self.games.len() >= LOBBY_CAPACITY {
+            return false;
+        }
+        self.games.push(entry);
+        true
+    }
+
+    fn update(&mut self, game: Pubkey, player_count: u8) {
+        if let Some(entry) = self.games.iter_mut().find(|entry| entry.game == game) {
+            entry.player_count = player_count;
+        }
+    }
+
+    fn remove(&mut self, game: Pubkey) {
+        self.games.retain(|entry| entry.game != game);
+    }
+}
+
+/// Ephemeral key a player has authorized to take turns for them in one game
+#[account]
+pub struct Session {
+    pub player: Pubkey,
+    pub game: Pubkey,
+    pub signer: Pubkey, // Session keypair allowed to sign turn instructions
+    pub expires_at: i64,
+    pub max_spend: u64, // Lamports the player funded the session key with
+    pub bump: u8,
+}
+
+impl Session {
+    pub const INIT_SPACE: usize = 32 +                                        // player
+        32 +                                        // game
+        32 +                                        // signer
+        8 +                                         // expires_at
+        8 +                                         // max_spend
+        1; // bump
+}
+
+/// Lifetime statistics of one wallet across every game, seeded by `[b"profile", player]`
+#[account]
+pub struct PlayerProfile {
+    pub player: Pubkey,
+    pub games_played: u64,
+    pub wins: u64, // Partners of a winning team both count the win
+    pub second_places: u64,
+    pub captures_made: u64,
+    pub captures_suffered: u64,
+    pub pieces_home: u64, // Own pieces brought home
+    pub total_wagered: u64, // In the wager units of each game, lamports or tokens
+    pub total_won: u64,
+    pub rating: u32, // Elo rating in hundredths of a point, see `rating`
+    pub bump: u8,
+}
+
+impl PlayerProfile {
+    pub const INIT_SPACE: usize = 32 +                                        // player
+        8 +                                         // games_played
+        8 +                                         // wins
+        8 +                                         // second_places
+        8 +                                         // captures_made
+        8 +                                         // captures_suffered
+        8 +                                         // pieces_home
+        8 +                                         // total_wagered
+        8 +                                         // total_won
+        4 +                                         // rating
+        1; // bump
+
+    /// Count a game the player has placed a bet in, filling in the profile on
+    /// its first game
+    fn record_join(&mut self, player: Pubkey, bump: u8, bet_amount: u64) {
+        if self.games_played == 0 {
+            self.rating = rating::INITIAL_RATING;
+        }
+        self.player = player;
+        self.bump = bump;
+        self.games_played += 1;
+        self.total_wagered += bet_amount;
+    }
+
+    /// Check the profile sits at the address seeded by its player
+    fn verify_address(profile: &Account<PlayerProfile>) -> Result<()> {
+        let address = Pubkey::create_program_address(
+            &[
+                b"profile".as_ref(),
+                profile.player.as_ref(),
+                &[profile.bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| ErrorCode::InvalidProfile)?;
+        require_keys_eq!(profile.key(), address, ErrorCode::InvalidProfile);
+        Ok(())
+    }
+}
+
+/// Admin-supplied settings for `initialize_config` and `update_config`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ConfigParams {
+    pub treasury: Pubkey,
+    pub fee_bps: u16,
+    pub min_bet: u64,
+    pub max_bet: u64,
+    pub allowed_player_counts: Vec<u8>,
+    pub turn_timeout: i64, // Seconds a player has to act before their turn can be claimed
+    pub max_missed_turns: u8,
+    pub oracles: Vec<Pubkey>, // At most MAX_ORACLES
+}
+
+#[event]
+pub struct GameCreated {
+    pub game: Pubkey,
+    pub game_id: u64,
+    pub creator: Pubkey,
+    pub max_players: u8,
+    pub bet_amount: u64,
+    pub mint: Option<Pubkey>,
+    pub oracle: Option<Pubkey>,
+    pub rules: RuleSet,
+    pub team_mode: TeamMode,
+    pub lobby_page: u32, // Page of the tier's lobby the game is listed in
+}
+
+#[event]
+pub struct PlayerJoined {
+    pub game: Pubkey,
+    pub player: Pubkey,
+    pub seat: u8,
+    pub total_bet: u64,
+}
+
+#[event]
+pub struct DiceRolled {
+    pub game: Pubkey,
+    pub seat: u8,
+    pub value: u8,
+}
+
+#[event]
+pub struct PieceMoved {
+    pub game: Pubkey,
+    pub seat: u8,
+    pub piece: u8,
+    pub from: u8,
+    pub to: u8,
+    pub dice_value: u8,
+}
+
+#[event]
+pub struct PieceCaptured {
+    pub game: Pubkey,
+    pub seat: u8,
+    pub piece: u8,
+    pub captured_seat: u8,
+    pub captured_piece: u8,
+}
+
+#[event]
+pub struct PieceHome {
+    pub game: Pubkey,
+    pub seat: u8,
+    pub piece: u8,
+    pub home_count: u8,
+}
+
+#[event]
+pub struct TurnSkipped {
+    pub game: Pubkey,
+    pub seat: u8,
+    pub reason: TurnSkipReason,
+}
+
+#[event]
+pub struct PlayerEjected {
+    pub game: Pubkey,
+    pub player: Pubkey,
+    pub seat: u8,
+}
+
+#[event]
+pub struct PlayerResigned {
+    pub game: Pubkey,
+    pub player: Pubkey,
+    pub seat: u8,
+}
+
+#[event]
+pub struct GameCompleted {
+    pub game: Pubkey,
+    pub winner: Option<Pubkey>,
+    pub second_place: Option<Pubkey>,
+}
+
+#[event]
+pub struct PrizesPaid {
+    pub game: Pubkey,
+    pub mint: Option<Pubkey>,
+    pub platform_fee: u64,
+    pub winner: Pubkey,
+    pub winner_amount: u64,
+    pub second_place: Option<Pubkey>,
+    pub second_place_amount: u64,
+}
+
+#[event]
+pub struct GameCancelled {
+    pub game: Pubkey,
+    pub refunded_players: u8,
+    pub refund_amount: u64,
+}
+
+#[event]
+pub struct SessionCreated {
+    pub game: Pubkey,
+    pub player: Pubkey,
+    pub signer: Pubkey,
+    pub expires_at: i64,
+    pub max_spend: u64,
+}
+
+#[event]
+pub struct SessionRevoked {
+    pub game: Pubkey,
+    pub player: Pubkey,
+    pub signer: Pubkey,
+}
+
+#[event]
+pub struct GameMigrated {
+    pub game: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum TurnSkipReason {
+    Timeout,
+    ThreeSixes,
+    NoLegalMoves,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
 pub enum GameStatus {
     WaitingForPlayers,
     Active,
@@ -640,11 +2853,35 @@ pub enum GameStatus {
     Cancelled,
 }
 
+impl GameStatus {
+    /// Decode the status byte stored on `GameState`
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => GameStatus::WaitingForPlayers,
+            1 => GameStatus::Active,
+            2 => GameStatus::Completed,
+            3 => GameStatus::Finalized,
+            _ => GameStatus::Cancelled,
+        }
+    }
+}
+
+impl From<MoveError> for ErrorCode {
+    fn from(error: MoveError) -> Self {
+        match error {
+            MoveError::InvalidPiece => ErrorCode::InvalidPiece,
+            MoveError::CannotStart => ErrorCode::CannotStart,
+            MoveError::InvalidMove => ErrorCode::InvalidMove,
+            MoveError::Blocked => ErrorCode::MoveBlocked,
+        }
+    }
+}
+
 #[error_code]
 pub enum ErrorCode {
-    #[msg("Invalid number of players. Must be 2 or 4.")]
+    #[msg("Invalid number of players for this program config.")]
     InvalidPlayerCount,
-    #[msg("Invalid bet amount. Must be greater than 0.")]
+    #[msg("Invalid bet amount. Must be within the configured limits.")]
     InvalidBetAmount,
     #[msg("It's not your turn.")]
     NotYourTurn,
@@ -682,4 +2919,200 @@ pub enum ErrorCode {
     InsufficientRandomness,
     #[msg("No valid moves available with current dice roll.")]
     NoValidMoves,
+    #[msg("A non-zero dice commitment is required.")]
+    MissingCommitment,
+    #[msg("A dice reveal is required to roll.")]
+    RevealMissing,
+    #[msg("Reveal does not match the committed hash.")]
+    RevealMismatch,
+    #[msg("Commitment cannot be rotated while it is your turn.")]
+    CommitmentLocked,
+    #[msg("You are not a player in this game.")]
+    NotAPlayer,
+    #[msg("This game rolls through its VRF oracle.")]
+    OracleRollRequired,
+    #[msg("Signer is not the oracle for this game.")]
+    InvalidOracle,
+    #[msg("Randomness has already been requested.")]
+    RandomnessAlreadyRequested,
+    #[msg("Dice has already been rolled.")]
+    DiceAlreadyRolled,
+    #[msg("Mint does not match the game's wager mint.")]
+    InvalidMint,
+    #[msg("Token accounts are required for token tables.")]
+    TokenAccountsMissing,
+    #[msg("Token account has the wrong owner or mint.")]
+    InvalidTokenAccount,
+    #[msg("A payout recipient account is missing or out of order.")]
+    MissingPayoutAccount,
+    #[msg("Game account does not hold enough lamports for this payout.")]
+    InsufficientGameFunds,
+    #[msg("Winner account does not match the game's recorded winner.")]
+    WinnerAccountMismatch,
+    #[msg("Second place account does not match the game's recorded second place.")]
+    SecondPlaceAccountMismatch,
+    #[msg("Signer is not the config admin.")]
+    InvalidAdmin,
+    #[msg("Platform fee exceeds the maximum.")]
+    InvalidFee,
+    #[msg("Platform account is not the configured treasury.")]
+    InvalidTreasury,
+    #[msg("Turn timeout and missed turn limit must be greater than 0.")]
+    InvalidTimeout,
+    #[msg("The current player has not timed out yet.")]
+    TurnNotTimedOut,
+    #[msg("You have already finished in a paid place.")]
+    AlreadyPlaced,
+    #[msg("Game account uses an older layout, call migrate_game first.")]
+    GameNeedsMigration,
+    #[msg("Game account is already on the current layout.")]
+    GameAlreadyMigrated,
+    #[msg("Game account layout version is not recognised.")]
+    UnsupportedGameVersion,
+    #[msg("Team mode needs a four-player table.")]
+    TeamModeNeedsFourPlayers,
+    #[msg("An opponent's blockade is in the way of this move.")]
+    MoveBlocked,
+    #[msg("Session key is not valid for this game or signer.")]
+    InvalidSession,
+    #[msg("Session key has expired.")]
+    SessionExpired,
+    #[msg("Session expiry must be in the future and at most 24 hours away.")]
+    InvalidSessionExpiry,
+    #[msg("This game keeps a move history; pass its history account.")]
+    HistoryMissing,
+    #[msg("Player profile does not belong to this player.")]
+    InvalidProfile,
+    #[msg("This game credits player profiles; pass the profile of every player involved.")]
+    MissingProfile,
+    #[msg("This game is listed in a lobby; pass its lobby account.")]
+    LobbyMissing,
+    #[msg("Lobby is not the one listing this game.")]
+    InvalidLobby,
+    #[msg("Roll was requested this slot; reveal it in a later slot.")]
+    RandomnessNotReady,
+    #[msg("Roll request has expired; the turn will time out.")]
+    RollRequestExpired,
+    #[msg("Oracle is not on the config's list of allowed oracles.")]
+    OracleNotAllowed,
+    #[msg("Config lists too many oracles or the default key.")]
+    InvalidOracleList,
+    #[msg("Wager mint has a Token-2022 extension that could lock the pot.")]
+    UnsupportedMintExtension,
+    #[msg("Move log has no room left; request a roll to grow it.")]
+    HistoryFull,
+    #[msg("This lobby page is full; list the game on the next page.")]
+    LobbyFull,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SlotHashes sysvar data holding `slots` newest first, each hashed to its slot
+    fn slot_hashes(slots: &[u64]) -> Vec<u8> {
+        let mut data = (slots.len() as u64).to_le_bytes().to_vec();
+        for slot in slots {
+            data.extend_from_slice(&slot.to_le_bytes());
+            data.extend_from_slice(&[*slot as u8; 32]);
+        }
+        data
+    }
+
+    /// Initialized Token-2022 mint data carrying `extensions`, each zero-filled.
+    /// Only fixed-size extensions can be laid out this way.
+    fn mint_with(extensions: &[ExtensionType]) -> Vec<u8> {
+        use anchor_spl::token_interface::spl_token_2022::extension::{
+            BaseStateWithExtensionsMut, StateWithExtensionsMut,
+        };
+
+        let account_len = |extensions: &[ExtensionType]| {
+            ExtensionType::try_calculate_account_len::<MintState>(extensions).unwrap()
+        };
+        let header_len = account_len(&[ExtensionType::Uninitialized]);
+        let mut data = vec![0; account_len(extensions)];
+        let mut mint =
+            StateWithExtensionsMut::<MintState>::unpack_uninitialized(&mut data).unwrap();
+        mint.base = MintState {
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        };
+        mint.pack_base();
+        mint.init_account_type().unwrap();
+
+        // Type and length of each extension, followed by its zeroed value
+        let mut tlv = mint.get_tlv_data_mut();
+        for extension in extensions {
+            let value_len = account_len(&[*extension]) - header_len;
+            tlv[..2].copy_from_slice(&<[u8; 2]>::from(*extension));
+            tlv[2..4].copy_from_slice(&(value_len as u16).to_le_bytes());
+            tlv = &mut tlv[4 + value_len..];
+        }
+        data
+    }
+
+    #[test]
+    fn plain_mints_are_accepted() {
+        assert!(check_mint_extensions(&mint_with(&[])).is_ok());
+        // Token metadata is variable-length and can't be laid out by `mint_with`
+        let fixed_size = ALLOWED_MINT_EXTENSIONS
+            .into_iter()
+            .filter(|extension| *extension != ExtensionType::TokenMetadata);
+        for extension in fixed_size.clone() {
+            assert!(check_mint_extensions(&mint_with(&[extension])).is_ok());
+        }
+        assert!(check_mint_extensions(&mint_with(&fixed_size.collect::<Vec<_>>())).is_ok());
+    }
+
+    #[test]
+    fn mints_that_can_lock_the_pot_are_rejected() {
+        for extension in [
+            ExtensionType::TransferFeeConfig,
+            ExtensionType::TransferHook,
+            ExtensionType::PermanentDelegate,
+            ExtensionType::NonTransferable,
+        ] {
+            assert_eq!(
+                check_mint_extensions(&mint_with(&[extension])).unwrap_err(),
+                ErrorCode::UnsupportedMintExtension.into()
+            );
+            assert_eq!(
+                check_mint_extensions(&mint_with(&[ExtensionType::MetadataPointer, extension]))
+                    .unwrap_err(),
+                ErrorCode::UnsupportedMintExtension.into()
+            );
+        }
+    }
+
+    #[test]
+    fn slot_hash_from_uses_the_request_slot() {
+        let data = slot_hashes(&[12, 11, 10, 9]);
+        assert_eq!(slot_hash_from(&data, 10).unwrap(), [10; 32]);
+    }
+
+    #[test]
+    fn slot_hash_from_steps_over_skipped_slots() {
+        let data = slot_hashes(&[14, 12, 9]);
+        assert_eq!(slot_hash_from(&data, 10).unwrap(), [12; 32]);
+    }
+
+    #[test]
+    fn slot_hash_from_waits_for_the_request_slot() {
+        let data = slot_hashes(&[9, 8]);
+        assert_eq!(
+            slot_hash_from(&data, 10).unwrap_err(),
+            ErrorCode::RandomnessNotReady.into()
+        );
+    }
+
+    #[test]
+    fn slot_hash_from_rejects_slots_that_dropped_off() {
+        let data = slot_hashes(&[14, 12, 11]);
+        assert_eq!(
+            slot_hash_from(&data, 10).unwrap_err(),
+            ErrorCode::RollRequestExpired.into()
+        );
+        assert_eq!(slot_hash_from(&data, 11).unwrap(), [11; 32]);
+    }
 }
@@ -0,0 +1,1520 @@
+//! Tests that run whole instructions through the program's entrypoint.
+//!
+//! Accounts are laid out in memory the way the runtime hands them to a program and
+//! the Clock and Rent sysvars come from syscall stubs, so instructions run exactly
+//! as deployed up to the point where they would call into another program. Of
+//! those calls, System Program account creation and transfers and SPL Token
+//! transfers are carried out by stubs standing in for those programs.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE},
+    hash::hash,
+    instruction::Instruction,
+    program_pack::Pack,
+    program_stubs, sysvar,
+};
+use anchor_lang::{system_program, Discriminator, InstructionData};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::token::spl_token::{
+    self,
+    instruction::TokenInstruction,
+    state::{Account as TokenAccountState, AccountState, Mint as MintState},
+};
+use std::cell::Cell;
+use std::sync::Once;
+
+use crate::history::{GameHistory, HistoryEntry};
+use crate::{
+    Config, ErrorCode, GameState, GameStatus, Lobby, LobbyEntry, PlayerProfile, RuleSet, Session,
+    LOBBY_CAPACITY, MAX_SESSION_SECONDS,
+};
+
+const NOW: i64 = 1_700_000_000;
+const SLOT: u64 = 5_000;
+const BET: u64 = 100_000_000;
+
+thread_local! {
+    /// Slot and unix timestamp the stubbed Clock reports to the current test
+    static CLOCK: Cell<(u64, i64)> = const { Cell::new((SLOT, NOW)) };
+}
+
+/// Move the Clock the current test's instructions see forward
+fn warp(slots: u64, seconds: i64) {
+    CLOCK.with(|clock| {
+        let (slot, now) = clock.get();
+        clock.set((slot + slots, now + seconds));
+    });
+}
+
+/// Clock, Rent and the programs called into for instructions run outside a validator
+struct Sysvars;
+
+impl program_stubs::SyscallStubs for Sysvars {
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let (slot, unix_timestamp) = CLOCK.with(Cell::get);
+        let clock = Clock {
+            slot,
+            unix_timestamp,
+            ..Clock::default()
+        };
+        unsafe { *(var_addr as *mut Clock) = clock };
+        0
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        0
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let account = |index: usize| {
+            let key = instruction.accounts[index].pubkey;
+            account_infos
+                .iter()
+                .find(|info| *info.key == key)
+                .ok_or(ProgramError::NotEnoughAccountKeys)
+        };
+        // Program addresses sign through their seeds, wallets with their own signature
+        let signed = |info: &AccountInfo| {
+            info.is_signer
+                || signers_seeds
+                    .iter()
+                    .any(|seeds| Pubkey::create_program_address(seeds, &crate::ID) == Ok(*info.key))
+        };
+
+        if instruction.program_id == system_program::ID {
+            invoke_system_program(&instruction.data, account, signed)
+        } else if instruction.program_id == spl_token::ID {
+            invoke_token_program(&instruction.data, account, signed)
+        } else {
+            Err(ProgramError::IncorrectProgramId)
+        }
+    }
+}
+
+/// Carry out the System Program instructions the program and Anchor send on its
+/// behalf: creating an account and transferring lamports
+fn invoke_system_program<'a, 'info>(
+    data: &[u8],
+    account: impl Fn(usize) -> std::result::Result<&'a AccountInfo<'info>, ProgramError>,
+    signed: impl Fn(&AccountInfo) -> bool,
+) -> ProgramResult
+where
+    'info: 'a,
+{
+    let u64_at = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+    // CreateAccount { lamports, space, owner } or Transfer { lamports }
+    let create = match u32::from_le_bytes(data[..4].try_into().unwrap()) {
+        0 => true,
+        2 => false,
+        _ => return Err(ProgramError::InvalidInstructionData),
+    };
+    let (from, to) = (account(0)?, account(1)?);
+    if !signed(from) || (create && !signed(to)) {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if create && (to.data_len() != 0 || *to.owner != system_program::ID) {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let lamports = u64_at(4);
+    **from.try_borrow_mut_lamports()? = from
+        .lamports()
+        .checked_sub(lamports)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    **to.try_borrow_mut_lamports()? += lamports;
+    if create {
+        to.resize(u64_at(12) as usize)?;
+        to.assign(&Pubkey::try_from(&data[20..52]).unwrap());
+    }
+    Ok(())
+}
+
+/// Carry out the `transfer_checked` calls the program makes into SPL Token, with
+/// the checks the token program would make
+fn invoke_token_program<'a, 'info>(
+    data: &[u8],
+    account: impl Fn(usize) -> std::result::Result<&'a AccountInfo<'info>, ProgramError>,
+    signed: impl Fn(&AccountInfo) -> bool,
+) -> ProgramResult
+where
+    'info: 'a,
+{
+    let TokenInstruction::TransferChecked { amount, decimals } = TokenInstruction::unpack(data)?
+    else {
+        return Err(ProgramError::InvalidInstructionData);
+    };
+    let (source, mint, destination, authority) =
+        (account(0)?, account(1)?, account(2)?, account(3)?);
+
+    let mut from = TokenAccountState::unpack(&source.try_borrow_data()?)?;
+    let mut to = TokenAccountState::unpack(&destination.try_borrow_data()?)?;
+    if !signed(authority) || from.owner != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if from.mint != *mint.key
+        || to.mint != *mint.key
+        || MintState::unpack(&mint.try_borrow_data()?)?.decimals != decimals
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    from.amount = from
+        .amount
+        .checked_sub(amount)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    to.amount += amount;
+    TokenAccountState::pack(from, &mut source.try_borrow_mut_data()?)?;
+    TokenAccountState::pack(to, &mut destination.try_borrow_mut_data()?)
+}
+
+/// An account the test owns and lends to the program for one instruction
+struct TestAccount {
+    key: Pubkey,
+    owner: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+    signer: bool,
+    writable: bool,
+    executable: bool,
+}
+
+impl TestAccount {
+    fn wallet(key: Pubkey) -> Self {
+        TestAccount {
+            key,
+            owner: system_program::ID,
+            lamports: 0,
+            data: Vec::new(),
+            signer: false,
+            writable: true,
+            executable: false,
+        }
+    }
+
+    fn signer(key: Pubkey) -> Self {
+        TestAccount {
+            signer: true,
+            lamports: 1_000_000_000,
+            ..TestAccount::wallet(key)
+        }
+    }
+
+    /// An executable program, also standing in for omitted optional accounts
+    fn program(key: Pubkey) -> Self {
+        TestAccount {
+            writable: false,
+            executable: true,
+            ..TestAccount::wallet(key)
+        }
+    }
+
+    /// A zero-copy account of this program, funded with rent plus `extra` lamports
+    fn zero_copy<T: bytemuck::Pod + Discriminator>(key: Pubkey, value: &T, extra: u64) -> Self {
+        let mut data = T::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(bytemuck::bytes_of(value));
+        Self::owned(key, data, extra)
+    }
+
+    /// A Borsh account of this program with `space` bytes after the discriminator
+    fn borsh<T: AccountSerialize>(key: Pubkey, value: &T, space: usize) -> Self {
+        let mut data = Vec::new();
+        value.try_serialize(&mut data).unwrap();
+        data.resize(8 + space, 0);
+        Self::owned(key, data, 0)
+    }
+
+    fn owned(key: Pubkey, data: Vec<u8>, extra: u64) -> Self {
+        TestAccount {
+            owner: crate::ID,
+            lamports: Rent::default().minimum_balance(data.len()) + extra,
+            data,
+            ..TestAccount::wallet(key)
+        }
+    }
+
+    /// An SPL Token mint with six decimals
+    fn mint(key: Pubkey) -> Self {
+        let mut data = vec![0; MintState::LEN];
+        let mint = MintState {
+            decimals: 6,
+            is_initialized: true,
+            ..MintState::default()
+        };
+        MintState::pack(mint, &mut data).unwrap();
+        Self::token_program_owned(key, data)
+    }
+
+    /// A token account of `mint` owned by `owner` and holding `amount`
+    fn token(key: Pubkey, mint: Pubkey, owner: Pubkey, amount: u64) -> Self {
+        let mut data = vec![0; TokenAccountState::LEN];
+        let account = TokenAccountState {
+            mint,
+            owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccountState::default()
+        };
+        TokenAccountState::pack(account, &mut data).unwrap();
+        Self::token_program_owned(key, data)
+    }
+
+    fn token_program_owned(key: Pubkey, data: Vec<u8>) -> Self {
+        TestAccount {
+            owner: spl_token::ID,
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            ..TestAccount::wallet(key)
+        }
+    }
+
+    fn game(&self) -> GameState {
+        bytemuck::pod_read_unaligned(&self.data[8..8 + GameState::SIZE])
+    }
+
+    fn token_amount(&self) -> u64 {
+        TokenAccountState::unpack(&self.data).unwrap().amount
+    }
+}
+
+/// Original data length of an account, stored just before its key
+#[repr(C)]
+struct KeyPrefix {
+    _padding: u32,
+    original_data_len: u32,
+    key: Pubkey,
+}
+
+/// A `TestAccount` laid out as the runtime lays accounts out for a program, which
+/// `AccountInfo::resize` relies on: the data length just before the data and room
+/// after it to grow into
+struct Serialized {
+    key: Box<KeyPrefix>,
+    owner: Pubkey,
+    lamports: u64,
+    data: Vec<u64>, // Data length, then the data, aligned as the runtime aligns it
+    signer: bool,
+    writable: bool,
+    executable: bool,
+}
+
+impl Serialized {
+    fn new(account: &TestAccount) -> Self {
+        let len = account.data.len();
+        let mut data = vec![0; 1 + (len + MAX_PERMITTED_DATA_INCREASE).div_ceil(8)];
+        let bytes: &mut [u8] = bytemuck::cast_slice_mut(&mut data);
+        bytes[..8].copy_from_slice(&(len as u64).to_le_bytes());
+        bytes[8..8 + len].copy_from_slice(&account.data);
+        Serialized {
+            key: Box::new(KeyPrefix {
+                _padding: 0,
+                original_data_len: len as u32,
+                key: account.key,
+            }),
+            owner: account.owner,
+            lamports: account.lamports,
+            data,
+            signer: account.signer,
+            writable: account.writable,
+            executable: account.executable,
+        }
+    }
+
+    fn data_len(&self) -> usize {
+        let bytes: &[u8] = bytemuck::cast_slice(&self.data);
+        u64::from_le_bytes(bytes[..8].try_into().unwrap()) as usize
+    }
+
+    fn info(&mut self) -> AccountInfo<'_> {
+        let len = self.data_len();
+        let bytes: &mut [u8] = bytemuck::cast_slice_mut(&mut self.data);
+        AccountInfo::new(
+            &self.key.key,
+            self.signer,
+            self.writable,
+            &mut self.lamports,
+            &mut bytes[8..8 + len],
+            &self.owner,
+            self.executable,
+            0,
+        )
+    }
+
+    /// Hand the account back with what the instruction left in it
+    fn store(&self, account: &mut TestAccount) {
+        let bytes: &[u8] = bytemuck::cast_slice(&self.data);
+        account.data = bytes[8..8 + self.data_len()].to_vec();
+        account.owner = self.owner;
+        account.lamports = self.lamports;
+    }
+}
+
+/// Run one instruction against `accounts`, in account-meta order
+fn run(accounts: &mut [TestAccount], data: &[u8]) -> ProgramResult {
+    static STUBS: Once = Once::new();
+    STUBS.call_once(|| {
+        program_stubs::set_syscall_stubs(Box::new(Sysvars));
+    });
+
+    let mut serialized: Vec<Serialized> = accounts.iter().map(Serialized::new).collect();
+    let result = {
+        let infos: Vec<AccountInfo> = serialized.iter_mut().map(Serialized::info).collect();
+        crate::entry(&crate::ID, &infos, data)
+    };
+    for (account, serialized) in accounts.iter_mut().zip(&serialized) {
+        serialized.store(account);
+    }
+    result
+}
+
+fn error(code: ErrorCode) -> ProgramError {
+    anchor_lang::error::Error::from(code).into()
+}
+
+/// SlotHashes sysvar holding the slots just before the current one
+fn slot_hashes_account() -> TestAccount {
+    let slots = [SLOT - 1, SLOT - 2];
+    let mut data = (slots.len() as u64).to_le_bytes().to_vec();
+    for slot in slots {
+        data.extend_from_slice(&slot.to_le_bytes());
+        data.extend_from_slice(&[slot as u8; 32]);
+    }
+    TestAccount {
+        owner: sysvar::ID,
+        data,
+        writable: false,
+        ..TestAccount::wallet(sysvar::slot_hashes::ID)
+    }
+}
+
+/// The player's profile at its seeded address, after their first game
+fn profile_account(player: Pubkey) -> TestAccount {
+    let (key, bump) =
+        Pubkey::find_program_address(&[b"profile".as_ref(), player.as_ref()], &crate::ID);
+    let profile = PlayerProfile {
+        player,
+        games_played: 1,
+        wins: 0,
+        second_places: 0,
+        captures_made: 0,
+        captures_suffered: 0,
+        pieces_home: 0,
+        total_wagered: BET,
+        total_won: 0,
+        rating: crate::rating::INITIAL_RATING,
+        bump,
+    };
+    TestAccount::borsh(key, &profile, PlayerProfile::INIT_SPACE)
+}
+
+fn config_account(treasury: Pubkey) -> TestAccount {
+    let config = Config {
+        admin: Pubkey::new_unique(),
+        treasury,
+        fee_bps: 1_000,
+        min_bet: 1,
+        max_bet: u64::MAX,
+        allowed_player_counts: vec![2, 3, 4, 6],
+        turn_timeout: 60,
+        max_missed_turns: 3,
+        oracles: Vec::new(),
+        bump: Pubkey::find_program_address(&[b"config".as_ref()], &crate::ID).1,
+    };
+    let key = Pubkey::find_program_address(&[b"config".as_ref()], &crate::ID).0;
+    TestAccount::borsh(key, &config, Config::INIT_SPACE)
+}
+
+/// Accounts for `distribute_prizes` on a SOL table, in account-meta order
+fn distribute_accounts(game: &GameState, treasury: Pubkey) -> Vec<TestAccount> {
+    let second_place = match game.second_place() {
+        Some(second_place) => TestAccount::wallet(second_place),
+        None => TestAccount::program(crate::ID),
+    };
+    vec![
+        TestAccount::zero_copy(Pubkey::new_unique(), game, game.total_bet),
+        config_account(treasury),
+        TestAccount::wallet(treasury),
+        TestAccount::wallet(game.winner().unwrap()),
+        second_place,
+        TestAccount::program(crate::ID), // mint
+        TestAccount::program(crate::ID), // vault
+        TestAccount::program(crate::ID), // platform_token_account
+        TestAccount::program(crate::ID), // winner_token_account
+        TestAccount::program(crate::ID), // second_place_token_account
+        TestAccount::program(crate::ID), // token_program
+        TestAccount::program(system_program::ID),
+    ]
+}
+
+#[test]
+fn distribute_prizes_pays_the_winner_and_the_platform() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let treasury = Pubkey::new_unique();
+    let mut game = GameState::seated(&players, BET, GameStatus::Completed);
+    game.winner = players[1];
+
+    let mut accounts = distribute_accounts(&game, treasury);
+    let game_lamports = accounts[0].lamports;
+    run(
+        &mut accounts,
+        &crate::instruction::DistributePrizes {}.data(),
+    )
+    .unwrap();
+
+    // 10% of the 2 SOL pot to the platform, the rest to the winner
+    assert_eq!(accounts[2].lamports, 20_000_000);
+    assert_eq!(accounts[3].lamports, 180_000_000);
+    assert_eq!(accounts[0].lamports, game_lamports - 200_000_000);
+    assert!(accounts[0].game().status() == GameStatus::Finalized);
+}
+
+#[test]
+fn distribute_prizes_rates_every_profile_when_the_game_keeps_profiles() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let treasury = Pubkey::new_unique();
+    let mut game = GameState::seated(&players, BET, GameStatus::Completed);
+    game.winner = players[1];
+    game.profiles = 1;
+    let data = crate::instruction::DistributePrizes {}.data();
+
+    // The loser can't leave the profiles out to dodge the rating
+    let mut accounts = distribute_accounts(&game, treasury);
+    assert_eq!(
+        run(&mut accounts, &data).unwrap_err(),
+        error(ErrorCode::MissingProfile)
+    );
+
+    let mut accounts = distribute_accounts(&game, treasury);
+    accounts.extend(players.map(profile_account));
+    run(&mut accounts, &data).unwrap();
+
+    let profile = |account: &TestAccount| {
+        PlayerProfile::try_deserialize(&mut account.data.as_slice()).unwrap()
+    };
+    let (loser, winner) = (profile(&accounts[12]), profile(&accounts[13]));
+    assert_eq!(winner.wins, 1);
+    assert_eq!(winner.total_won, 180_000_000);
+    assert!(winner.rating > crate::rating::INITIAL_RATING);
+    assert!(loser.rating < crate::rating::INITIAL_RATING);
+}
+
+#[test]
+fn distribute_prizes_pays_both_places_of_a_four_player_table() {
+    let players: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+    let treasury = Pubkey::new_unique();
+    let mut game = GameState::seated(&players, BET, GameStatus::Completed);
+    game.winner = players[2];
+    game.second_place = players[0];
+
+    let mut accounts = distribute_accounts(&game, treasury);
+    let game_lamports = accounts[0].lamports;
+    run(
+        &mut accounts,
+        &crate::instruction::DistributePrizes {}.data(),
+    )
+    .unwrap();
+
+    // Of the 4 SOL pot: 10% platform, 25% second place, the rest to the winner
+    assert_eq!(accounts[2].lamports, 40_000_000);
+    assert_eq!(accounts[3].lamports, 260_000_000);
+    assert_eq!(accounts[4].lamports, 100_000_000);
+    // Only rent is left behind
+    assert_eq!(accounts[0].lamports, game_lamports - 400_000_000);
+    assert_eq!(
+        accounts[0].lamports,
+        Rent::default().minimum_balance(8 + GameState::SIZE)
+    );
+}
+
+#[test]
+fn distribute_prizes_only_pays_out_once() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let treasury = Pubkey::new_unique();
+    let mut game = GameState::seated(&players, BET, GameStatus::Completed);
+    game.winner = players[0];
+
+    let mut accounts = distribute_accounts(&game, treasury);
+    let data = crate::instruction::DistributePrizes {}.data();
+    run(&mut accounts, &data).unwrap();
+    assert_eq!(
+        run(&mut accounts, &data).unwrap_err(),
+        error(ErrorCode::GameNotCompleted)
+    );
+    assert_eq!(accounts[3].lamports, 180_000_000);
+}
+
+#[test]
+fn distribute_prizes_never_pays_out_the_rent() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let treasury = Pubkey::new_unique();
+    let mut game = GameState::seated(&players, BET, GameStatus::Completed);
+    game.winner = players[0];
+
+    let mut accounts = distribute_accounts(&game, treasury);
+    // The game account is short of the bets it claims to hold
+    accounts[0].lamports -= 1;
+    assert_eq!(
+        run(
+            &mut accounts,
+            &crate::instruction::DistributePrizes {}.data()
+        )
+        .unwrap_err(),
+        error(ErrorCode::InsufficientGameFunds)
+    );
+}
+
+#[test]
+fn cancel_game_refunds_every_deposited_player() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let mut game = GameState::seated(&players, BET, GameStatus::WaitingForPlayers);
+    game.max_players = 4;
+
+    let mut accounts = vec![
+        TestAccount::zero_copy(Pubkey::new_unique(), &game, game.total_bet),
+        TestAccount::signer(players[0]),
+        TestAccount::program(crate::ID), // lobby, the game was never listed
+        TestAccount::program(crate::ID), // mint
+        TestAccount::program(crate::ID), // vault
+        TestAccount::program(crate::ID), // token_program
+        TestAccount::program(system_program::ID),
+        // Refund recipients in seat order
+        TestAccount::wallet(players[0]),
+        TestAccount::wallet(players[1]),
+    ];
+    let game_lamports = accounts[0].lamports;
+    run(&mut accounts, &crate::instruction::CancelGame {}.data()).unwrap();
+
+    assert_eq!(accounts[7].lamports, BET);
+    assert_eq!(accounts[8].lamports, BET);
+    assert_eq!(accounts[0].lamports, game_lamports - 2 * BET);
+    assert!(accounts[0].game().status() == GameStatus::Cancelled);
+}
+
+#[test]
+fn cancel_game_refunds_only_the_recorded_players() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let mut game = GameState::seated(&players, BET, GameStatus::WaitingForPlayers);
+    game.max_players = 4;
+
+    let mut accounts = vec![
+        TestAccount::zero_copy(Pubkey::new_unique(), &game, game.total_bet),
+        TestAccount::signer(players[0]),
+        TestAccount::program(crate::ID),
+        TestAccount::program(crate::ID),
+        TestAccount::program(crate::ID),
+        TestAccount::program(crate::ID),
+        TestAccount::program(system_program::ID),
+        TestAccount::wallet(players[0]),
+        TestAccount::wallet(Pubkey::new_unique()),
+    ];
+    assert_eq!(
+        run(&mut accounts, &crate::instruction::CancelGame {}.data()).unwrap_err(),
+        error(ErrorCode::MissingPayoutAccount)
+    );
+    assert_eq!(accounts[8].lamports, 0);
+}
+
+#[test]
+fn cancel_game_takes_the_game_out_of_the_lobby_page_holding_it() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let mut game = GameState::seated(&players, BET, GameStatus::WaitingForPlayers);
+    game.max_players = 4;
+    game.listed = 1;
+    let game_key = Pubkey::new_unique();
+    let lobby_page = |page: u32, games: &[Pubkey]| {
+        let lobby = Lobby {
+            mint: Pubkey::default(),
+            bet_amount: BET,
+            max_players: 4,
+            page,
+            bump: 0,
+            games: games
+                .iter()
+                .map(|game| LobbyEntry {
+                    game: *game,
+                    player_count: 2,
+                    rules: RuleSet::STANDARD.to_flags(),
+                    team_mode: 0,
+                })
+                .collect(),
+        };
+        TestAccount::borsh(
+            Lobby::address(None, BET, 4, page),
+            &lobby,
+            Lobby::INIT_SPACE,
+        )
+    };
+    let accounts = |lobby: TestAccount| {
+        vec![
+            TestAccount::zero_copy(game_key, &game, game.total_bet),
+            TestAccount::signer(players[0]),
+            lobby,
+            TestAccount::program(crate::ID), // mint
+            TestAccount::program(crate::ID), // vault
+            TestAccount::program(crate::ID), // token_program
+            TestAccount::program(system_program::ID),
+            TestAccount::wallet(players[0]),
+            TestAccount::wallet(players[1]),
+        ]
+    };
+    let data = crate::instruction::CancelGame {}.data();
+
+    // A full first page of other tables, so the game went on the second
+    let others: Vec<Pubkey> = (0..LOBBY_CAPACITY).map(|_| Pubkey::new_unique()).collect();
+    let mut wrong_page = accounts(lobby_page(0, &others));
+    assert_eq!(
+        run(&mut wrong_page, &data).unwrap_err(),
+        error(ErrorCode::InvalidLobby)
+    );
+
+    let mut accounts = accounts(lobby_page(1, &[others[0], game_key]));
+    run(&mut accounts, &data).unwrap();
+    let lobby = Lobby::try_deserialize(&mut accounts[2].data.as_slice()).unwrap();
+    assert!(lobby.games.iter().map(|entry| entry.game).eq([others[0]]));
+}
+
+/// Id of the token tables in these tests, seeding the game PDA that signs for
+/// its vault
+const TOKEN_GAME_ID: u64 = 7;
+
+/// A token table of `mint` at the address of game `TOKEN_GAME_ID`, with its vault
+fn token_game(players: &[Pubkey], status: GameStatus, mint: Pubkey) -> (Pubkey, GameState) {
+    let game_seed = TOKEN_GAME_ID.to_le_bytes();
+    let (key, bump) = Pubkey::find_program_address(&[b"game".as_ref(), &game_seed], &crate::ID);
+    let mut game = GameState::seated(players, BET, status);
+    game.seed[..game_seed.len()].copy_from_slice(&game_seed);
+    game.seed_len = game_seed.len() as u8;
+    game.bump = bump;
+    game.mint = mint;
+    (key, game)
+}
+
+fn vault_account(game: Pubkey, mint: Pubkey, amount: u64) -> TestAccount {
+    let key = get_associated_token_address_with_program_id(&game, &mint, &spl_token::ID);
+    TestAccount::token(key, mint, game, amount)
+}
+
+#[test]
+fn join_game_deposits_the_bet_into_the_vault() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let mint = Pubkey::new_unique();
+    let (game_key, mut game) = token_game(&players, GameStatus::WaitingForPlayers, mint);
+    // The second seat is still open
+    game.players[1] = Pubkey::default();
+    game.deposited_players[1] = Pubkey::default();
+    game.player_count = 1;
+    game.total_bet = BET;
+
+    let mut accounts = vec![
+        TestAccount::zero_copy(game_key, &game, 0),
+        TestAccount::signer(players[1]),
+        profile_account(players[1]),
+        TestAccount::program(crate::ID), // lobby, the game was never listed
+        TestAccount::mint(mint),
+        vault_account(game_key, mint, BET),
+        TestAccount::token(Pubkey::new_unique(), mint, players[1], 5 * BET),
+        TestAccount::program(spl_token::ID),
+        TestAccount::program(system_program::ID),
+    ];
+    run(
+        &mut accounts,
+        &crate::instruction::JoinGame {
+            commitment: [7; 32],
+        }
+        .data(),
+    )
+    .unwrap();
+
+    assert_eq!(accounts[5].token_amount(), 2 * BET);
+    assert_eq!(accounts[6].token_amount(), 4 * BET);
+    let game = accounts[0].game();
+    assert_eq!(game.total_bet, 2 * BET);
+    assert_eq!(game.players[1], players[1]);
+    assert!(game.status() == GameStatus::Active);
+}
+
+#[test]
+fn distribute_prizes_pays_token_tables_out_of_the_vault() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let treasury = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let (game_key, mut game) = token_game(&players, GameStatus::Completed, mint);
+    game.winner = players[1];
+
+    let accounts = |winner_token_owner: Pubkey| {
+        vec![
+            TestAccount::zero_copy(game_key, &game, 0),
+            config_account(treasury),
+            TestAccount::wallet(treasury),
+            TestAccount::wallet(players[1]),
+            TestAccount::program(crate::ID), // second_place
+            TestAccount::mint(mint),
+            vault_account(game_key, mint, 2 * BET),
+            TestAccount::token(Pubkey::new_unique(), mint, treasury, 0),
+            TestAccount::token(Pubkey::new_unique(), mint, winner_token_owner, 0),
+            TestAccount::program(crate::ID), // second_place_token_account
+            TestAccount::program(spl_token::ID),
+            TestAccount::program(system_program::ID),
+        ]
+    };
+    let data = crate::instruction::DistributePrizes {}.data();
+
+    // The prize only goes to a token account the winner owns
+    let mut redirected = accounts(players[0]);
+    assert_eq!(
+        run(&mut redirected, &data).unwrap_err(),
+        error(ErrorCode::InvalidTokenAccount)
+    );
+
+    let mut accounts = accounts(players[1]);
+    run(&mut accounts, &data).unwrap();
+
+    // 10% of the 2-bet pot to the platform, the rest to the winner
+    assert_eq!(accounts[7].token_amount(), 20_000_000);
+    assert_eq!(accounts[8].token_amount(), 180_000_000);
+    assert_eq!(accounts[6].token_amount(), 0);
+    assert!(accounts[0].game().status() == GameStatus::Finalized);
+}
+
+#[test]
+fn cancel_game_refunds_token_bets_from_the_vault() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let mint = Pubkey::new_unique();
+    let (game_key, mut game) = token_game(&players, GameStatus::WaitingForPlayers, mint);
+    game.max_players = 4;
+
+    let mut accounts = vec![
+        TestAccount::zero_copy(game_key, &game, 0),
+        TestAccount::signer(players[0]),
+        TestAccount::program(crate::ID), // lobby, the game was never listed
+        TestAccount::mint(mint),
+        vault_account(game_key, mint, 2 * BET),
+        TestAccount::program(spl_token::ID),
+        TestAccount::program(system_program::ID),
+        // Refund token accounts in seat order
+        TestAccount::token(Pubkey::new_unique(), mint, players[0], 0),
+        TestAccount::token(Pubkey::new_unique(), mint, players[1], 0),
+    ];
+    run(&mut accounts, &crate::instruction::CancelGame {}.data()).unwrap();
+
+    assert_eq!(accounts[7].token_amount(), BET);
+    assert_eq!(accounts[8].token_amount(), BET);
+    assert_eq!(accounts[4].token_amount(), 0);
+    assert!(accounts[0].game().status() == GameStatus::Cancelled);
+}
+
+/// Accounts for `roll_and_move` by the seat holding the turn, with a roll
+/// requested in the previous slot against the hash chain of `reveal`
+fn roll_and_move_accounts(game: &mut GameState, reveal: [u8; 32]) -> Vec<TestAccount> {
+    let seat = game.turn as usize;
+    game.commitments[seat] = hash(&reveal).to_bytes();
+    game.randomness_requested = 1;
+    game.randomness_request_slot = SLOT - 1;
+    vec![
+        TestAccount::zero_copy(Pubkey::new_unique(), game, game.total_bet),
+        TestAccount::signer(game.players[seat]),
+        TestAccount::program(crate::ID), // session
+        TestAccount::program(crate::ID), // history
+        TestAccount::program(crate::ID), // profile
+        slot_hashes_account(),
+        TestAccount::program(system_program::ID),
+    ]
+}
+
+#[test]
+fn roll_and_move_plays_a_legal_move_the_priorities_left_out() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let reveal = [3; 32];
+
+    for priorities in [vec![], vec![9], vec![2]] {
+        let mut game = GameState::seated(&players, BET, GameStatus::Active);
+        // Every piece is out, so whatever the roll every piece has a move
+        game.positions[0] = [2, 3, 4, 5];
+
+        let mut accounts = roll_and_move_accounts(&mut game, reveal);
+        let data = crate::instruction::RollAndMove {
+            reveal,
+            priorities: priorities.clone(),
+        }
+        .data();
+        run(&mut accounts, &data).unwrap();
+
+        let played = accounts[0].game();
+        let moved: Vec<usize> = (0..4)
+            .filter(|piece| played.positions[0][*piece] != game.positions[0][*piece])
+            .collect();
+        let expected = if priorities == [2] { 2 } else { 0 };
+        assert_eq!(moved, [expected], "priorities {priorities:?}");
+        assert_eq!(played.dice_roll(), None);
+        assert_eq!(played.randomness_requested, 0);
+    }
+}
+
+#[test]
+fn roll_and_move_needs_a_roll_request_from_an_earlier_slot() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let reveal = [3; 32];
+    let data = crate::instruction::RollAndMove {
+        reveal,
+        priorities: vec![],
+    }
+    .data();
+
+    let mut game = GameState::seated(&players, BET, GameStatus::Active);
+    let mut accounts = roll_and_move_accounts(&mut game, reveal);
+    accounts[0] = {
+        let mut game = accounts[0].game();
+        game.randomness_requested = 0;
+        TestAccount::zero_copy(accounts[0].key, &game, game.total_bet)
+    };
+    assert_eq!(
+        run(&mut accounts, &data).unwrap_err(),
+        error(ErrorCode::RandomnessNotRequested)
+    );
+
+    let mut game = GameState::seated(&players, BET, GameStatus::Active);
+    let mut accounts = roll_and_move_accounts(&mut game, reveal);
+    accounts[0] = {
+        let mut game = accounts[0].game();
+        game.randomness_request_slot = SLOT;
+        TestAccount::zero_copy(accounts[0].key, &game, game.total_bet)
+    };
+    assert_eq!(
+        run(&mut accounts, &data).unwrap_err(),
+        error(ErrorCode::RandomnessNotReady)
+    );
+}
+
+#[test]
+fn roll_and_move_needs_the_movers_profile_when_the_game_keeps_profiles() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let reveal = [3; 32];
+    let data = crate::instruction::RollAndMove {
+        reveal,
+        priorities: vec![],
+    }
+    .data();
+    let mut game = GameState::seated(&players, BET, GameStatus::Active);
+    game.profiles = 1;
+    game.positions[0] = [2, 3, 4, 5];
+
+    let mut accounts = roll_and_move_accounts(&mut game, reveal);
+    assert_eq!(
+        run(&mut accounts, &data).unwrap_err(),
+        error(ErrorCode::MissingProfile)
+    );
+
+    // A profile of the right player at an address not seeded by them
+    let mut accounts = roll_and_move_accounts(&mut game, reveal);
+    accounts[4] = profile_account(players[0]);
+    accounts[4].key = Pubkey::new_unique();
+    assert_eq!(
+        run(&mut accounts, &data).unwrap_err(),
+        error(ErrorCode::InvalidProfile)
+    );
+
+    let mut accounts = roll_and_move_accounts(&mut game, reveal);
+    accounts[4] = profile_account(players[0]);
+    run(&mut accounts, &data).unwrap();
+    assert!(accounts[0].game().positions[0] != game.positions[0]);
+}
+
+#[test]
+fn rotate_commitment_is_refused_while_a_roll_is_pending() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let reveal = [7; 32];
+    let data = crate::instruction::RotateCommitment {
+        reveal,
+        commitment: [8; 32],
+    }
+    .data();
+    let mut game = GameState::seated(&players, BET, GameStatus::Active);
+    game.commitments[1] = hash(&reveal).to_bytes();
+    game.randomness_requested = 1;
+    game.randomness_request_slot = SLOT - 1;
+
+    // Seat 1 mixing a reveal in between seat 0's request and reveal
+    let mut accounts = vec![
+        TestAccount::zero_copy(Pubkey::new_unique(), &game, 0),
+        TestAccount::signer(players[1]),
+    ];
+    assert_eq!(
+        run(&mut accounts, &data).unwrap_err(),
+        error(ErrorCode::RandomnessAlreadyRequested)
+    );
+
+    game.randomness_requested = 0;
+    let mut accounts = vec![
+        TestAccount::zero_copy(Pubkey::new_unique(), &game, 0),
+        TestAccount::signer(players[1]),
+    ];
+    run(&mut accounts, &data).unwrap();
+    assert_eq!(accounts[0].game().commitments[1], [8; 32]);
+}
+
+/// Move log of `game` at its seeded address, with room for `capacity` entries of
+/// which the first `count` are taken
+fn history_account(game: Pubkey, count: usize, capacity: usize) -> TestAccount {
+    let key = Pubkey::find_program_address(&[b"history".as_ref(), game.as_ref()], &crate::ID).0;
+    let header = GameHistory {
+        game,
+        count: count as u64,
+    };
+    let mut account = TestAccount::zero_copy(key, &header, 0);
+    account.data.resize(GameHistory::space(capacity), 0);
+    account.lamports = Rent::default().minimum_balance(account.data.len());
+    account
+}
+
+#[test]
+fn move_piece_takes_the_last_entry_a_log_has_room_for() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let mut game = GameState::seated(&players, BET, GameStatus::Active);
+    game.history = 1;
+    game.positions[0] = [2, 3, 4, 5];
+    game.dice_roll = 3;
+
+    // The roll took the second to last entry
+    let game_key = Pubkey::new_unique();
+    let mut accounts = vec![
+        TestAccount::zero_copy(game_key, &game, 0),
+        TestAccount::signer(players[0]),
+        TestAccount::program(crate::ID), // session
+        history_account(game_key, 3, 4),
+        TestAccount::program(crate::ID), // profile
+    ];
+    let data = crate::instruction::MovePiece { piece_index: 0 }.data();
+    run(&mut accounts, &data).unwrap();
+
+    assert_eq!(accounts[0].game().positions[0], [5, 3, 4, 5]);
+    assert_eq!(GameHistory::header(&accounts[3].data).count, 4);
+    assert_eq!(
+        GameHistory::entries(&accounts[3].data).unwrap()[3],
+        HistoryEntry::moved(0, 3, &game.board().apply_move(0, 0, 3).unwrap(), NOW)
+    );
+}
+
+#[test]
+fn roll_and_move_requests_the_roll_of_the_seat_the_turn_passes_to() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let reveal = [3; 32];
+    let mut game = GameState::seated(&players, BET, GameStatus::Active);
+    game.positions[0] = [2, 3, 4, 5];
+    game.commitments[1] = [9; 32];
+
+    let mut accounts = roll_and_move_accounts(&mut game, reveal);
+    let data = crate::instruction::RollAndMove {
+        reveal,
+        priorities: vec![],
+    }
+    .data();
+    run(&mut accounts, &data).unwrap();
+
+    // Whoever holds the turn next can reveal from the next slot on
+    let played = accounts[0].game();
+    assert_eq!(played.dice_roll(), None);
+    assert_eq!(played.randomness_requested, 1);
+    assert_eq!(played.randomness_request_slot, SLOT);
+
+    // A seat still without a hash chain asks through `request_roll`
+    game.commitments[1] = [0; 32];
+    let mut accounts = roll_and_move_accounts(&mut game, reveal);
+    run(&mut accounts, &data).unwrap();
+    let played = accounts[0].game();
+    assert_eq!(played.randomness_requested, u8::from(played.turn == 0));
+}
+
+/// Stand-in for a VRF oracle: fulfills roll requests of games that name it,
+/// signing the callback with its authority
+struct MockOracle {
+    authority: Pubkey,
+}
+
+impl MockOracle {
+    fn new() -> Self {
+        MockOracle {
+            authority: Pubkey::new_unique(),
+        }
+    }
+
+    fn fulfill(&self, game: &mut TestAccount, randomness: [u8; 32]) -> ProgramResult {
+        let mut accounts = [
+            std::mem::replace(game, TestAccount::wallet(Pubkey::default())),
+            TestAccount::signer(self.authority),
+            TestAccount::program(crate::ID), // history
+        ];
+        let data = crate::instruction::FulfillRandomness { randomness }.data();
+        let result = run(&mut accounts, &data);
+        *game = std::mem::replace(&mut accounts[0], TestAccount::wallet(Pubkey::default()));
+        result
+    }
+}
+
+/// A game rolling through `oracle`, with every piece of the first seat out so
+/// any roll can be played
+fn oracle_game(players: &[Pubkey], oracle: &MockOracle) -> GameState {
+    let mut game = GameState::seated(players, BET, GameStatus::Active);
+    game.oracle = oracle.authority;
+    game.positions[0] = [2, 3, 4, 5];
+    game
+}
+
+/// `game` as an account, with a roll requested in `request_slot`
+fn requested_roll(mut game: GameState, request_slot: u64) -> TestAccount {
+    game.randomness_requested = 1;
+    game.randomness_request_slot = request_slot;
+    TestAccount::zero_copy(Pubkey::new_unique(), &game, game.total_bet)
+}
+
+#[test]
+fn oracle_fulfills_a_requested_roll() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let oracle = MockOracle::new();
+    let game = oracle_game(&players, &oracle);
+    let mut accounts = vec![
+        TestAccount::zero_copy(Pubkey::new_unique(), &game, game.total_bet),
+        TestAccount::signer(players[0]),
+        TestAccount::program(crate::ID), // session
+        TestAccount::program(crate::ID), // history
+        TestAccount::program(system_program::ID),
+    ];
+    run(&mut accounts, &crate::instruction::RequestRoll {}.data()).unwrap();
+    assert_eq!(accounts[0].game().randomness_requested, 1);
+
+    warp(1, 1);
+    oracle.fulfill(&mut accounts[0], [5; 32]).unwrap();
+    let game = accounts[0].game();
+    assert_eq!(game.randomness_requested, 0);
+    assert_eq!(game.dice_roll(), Some(crate::dice_from_entropy(&[5; 32])));
+}
+
+#[test]
+fn fulfill_randomness_needs_the_games_oracle() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let oracle = MockOracle::new();
+    let mut game = requested_roll(oracle_game(&players, &oracle), SLOT - 1);
+
+    assert_eq!(
+        MockOracle::new().fulfill(&mut game, [5; 32]).unwrap_err(),
+        error(ErrorCode::InvalidOracle)
+    );
+    assert_eq!(game.game().dice_roll(), None);
+}
+
+#[test]
+fn fulfill_randomness_only_lands_inside_the_request_window() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let oracle = MockOracle::new();
+
+    // In the slot of the request, whose hash is not settled yet
+    let mut game = requested_roll(oracle_game(&players, &oracle), SLOT);
+    assert_eq!(
+        oracle.fulfill(&mut game, [5; 32]).unwrap_err(),
+        error(ErrorCode::RandomnessNotReady)
+    );
+
+    let expired = SLOT - crate::ROLL_REQUEST_WINDOW_SLOTS - 1;
+    let mut game = requested_roll(oracle_game(&players, &oracle), expired);
+    assert_eq!(
+        oracle.fulfill(&mut game, [5; 32]).unwrap_err(),
+        error(ErrorCode::RollRequestExpired)
+    );
+}
+
+/// A table in play whose turn holder last acted at `NOW`, with every seat
+/// committed to a hash chain
+fn timed_game(players: &[Pubkey]) -> GameState {
+    let mut game = GameState::seated(players, BET, GameStatus::Active);
+    game.last_move_time = NOW;
+    for seat in 0..players.len() {
+        game.commitments[seat] = hash(&[seat as u8; 32]).to_bytes();
+    }
+    game
+}
+
+/// Run `claim_timeout` against `game` and return the game it left behind
+fn claim_timeout(game: &GameState) -> std::result::Result<GameState, ProgramError> {
+    let mut accounts = vec![
+        TestAccount::zero_copy(Pubkey::new_unique(), game, game.total_bet),
+        TestAccount::signer(Pubkey::new_unique()),
+        TestAccount::program(crate::ID), // history
+        TestAccount::program(system_program::ID),
+    ];
+    run(&mut accounts, &crate::instruction::ClaimTimeout {}.data())?;
+    Ok(accounts[0].game())
+}
+
+#[test]
+fn claim_timeout_records_a_missed_turn_and_passes_it_on() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let mut game = timed_game(&players);
+    game.dice_roll = 3;
+
+    assert_eq!(
+        claim_timeout(&game).err(),
+        Some(error(ErrorCode::TurnNotTimedOut))
+    );
+
+    warp(150, 61);
+    let game = claim_timeout(&game).unwrap();
+    assert_eq!(&game.missed_turns[..2], &[1, 0]);
+    assert_eq!(game.players[0], players[0]);
+    assert_eq!(game.turn, 1);
+    assert_eq!(game.dice_roll(), None);
+    assert_eq!(game.last_move_time, NOW + 61);
+    // The next seat's roll is requested straight away
+    assert_eq!(game.randomness_requested, 1);
+    assert_eq!(game.randomness_request_slot, SLOT + 150);
+}
+
+#[test]
+fn claim_timeout_passes_the_turn_over_seats_out_of_play() {
+    let players: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+    let mut game = timed_game(&players);
+    game.turn = 3;
+    // Seat 0 was ejected and seat 1 has brought every piece home
+    game.players[0] = Pubkey::default();
+    game.home_counts[1] = 4;
+    game.winner = players[1];
+
+    warp(1, 61);
+    let game = claim_timeout(&game).unwrap();
+    assert_eq!(game.missed_turns[3], 1);
+    assert_eq!(game.turn, 2);
+}
+
+#[test]
+fn claim_timeout_ejects_a_player_after_the_allowed_misses() {
+    let players: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+    let mut game = timed_game(&players);
+    game.missed_turns[0] = 2;
+
+    warp(1, 61);
+    let game = claim_timeout(&game).unwrap();
+    assert_eq!(game.missed_turns[0], 3);
+    assert_eq!(game.players[0], Pubkey::default());
+    assert_eq!(game.deposited_players[0], players[0]);
+    assert!(game.status() == GameStatus::Active);
+    assert_eq!(game.turn, 1);
+
+    // Ejecting one of the last two players ends the game
+    let mut game = game;
+    game.last_move_time = NOW + 61;
+    game.missed_turns[1] = 2;
+    warp(1, 61);
+    let game = claim_timeout(&game).unwrap();
+    assert!(game.status() == GameStatus::Completed);
+    assert_eq!(game.winner(), Some(players[2]));
+}
+
+/// Have `player` resign from `game` and return the game it left behind
+fn resign(game: &GameState, player: Pubkey) -> std::result::Result<GameState, ProgramError> {
+    let mut accounts = vec![
+        TestAccount::zero_copy(Pubkey::new_unique(), game, game.total_bet),
+        TestAccount::signer(player),
+        TestAccount::program(crate::ID), // history
+        TestAccount::program(system_program::ID),
+    ];
+    run(&mut accounts, &crate::instruction::Resign {}.data())?;
+    Ok(accounts[0].game())
+}
+
+#[test]
+fn resign_leaves_the_game_to_the_players_still_in_it() {
+    let players: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+    let game = timed_game(&players);
+
+    // Three players are still competing, so nobody is placed yet
+    let game = resign(&game, players[0]).unwrap();
+    assert!(game.status() == GameStatus::Active);
+    assert_eq!(game.players[0], Pubkey::default());
+    assert_eq!((game.winner(), game.second_place()), (None, None));
+    // The turn moved on from the resigned seat
+    assert_eq!(game.turn, 1);
+    assert_eq!(game.randomness_requested, 1);
+
+    // A resigned player can't resign again
+    assert_eq!(
+        resign(&game, players[0]).err(),
+        Some(error(ErrorCode::NotAPlayer))
+    );
+}
+
+#[test]
+fn resign_places_the_last_two_contenders() {
+    let players: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+    let mut game = timed_game(&players);
+    game.players[3] = Pubkey::default();
+
+    // With no winner yet, the last contender wins and the resigning player is second
+    let mut unplaced = game;
+    unplaced.players[0] = Pubkey::default();
+    let placed = resign(&unplaced, players[1]).unwrap();
+    assert!(placed.status() == GameStatus::Completed);
+    assert_eq!(placed.winner(), Some(players[2]));
+    assert_eq!(placed.second_place(), Some(players[1]));
+
+    // Behind a winner, the last contender takes second place
+    game.winner = players[0];
+    game.home_counts[0] = 4;
+    let placed = resign(&game, players[1]).unwrap();
+    assert!(placed.status() == GameStatus::Completed);
+    assert_eq!(placed.winner(), Some(players[0]));
+    assert_eq!(placed.second_place(), Some(players[2]));
+
+    // The winner has nothing left to concede
+    assert_eq!(
+        resign(&game, players[0]).err(),
+        Some(error(ErrorCode::AlreadyPlaced))
+    );
+}
+
+#[test]
+fn resign_hands_a_two_player_game_to_the_opponent() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let game = resign(&timed_game(&players), players[1]).unwrap();
+    assert!(game.status() == GameStatus::Completed);
+    assert_eq!(game.winner(), Some(players[0]));
+    assert_eq!(game.second_place(), None);
+}
+
+#[test]
+fn move_piece_completes_the_game_when_the_winner_leaves_one_contender() {
+    let players: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+    let mut game = timed_game(&players);
+    // Seats 1 and 2 were ejected, and seat 0 has one piece left to bring home
+    game.players[1] = Pubkey::default();
+    game.players[2] = Pubkey::default();
+    game.home_counts[0] = 3;
+    game.positions[0] = [
+        game.track_length - 2,
+        game.track_length,
+        game.track_length,
+        game.track_length,
+    ];
+    game.dice_roll = 2;
+
+    let mut accounts = vec![
+        TestAccount::zero_copy(Pubkey::new_unique(), &game, game.total_bet),
+        TestAccount::signer(players[0]),
+        TestAccount::program(crate::ID), // session
+        TestAccount::program(crate::ID), // history
+        TestAccount::program(crate::ID), // profile
+    ];
+    run(
+        &mut accounts,
+        &crate::instruction::MovePiece { piece_index: 0 }.data(),
+    )
+    .unwrap();
+
+    let game = accounts[0].game();
+    assert!(game.status() == GameStatus::Completed);
+    assert_eq!(game.winner(), Some(players[0]));
+    assert_eq!(game.second_place(), Some(players[3]));
+}
+
+#[test]
+fn partners_win_together_and_split_the_pot() {
+    let players: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+    let mut game = timed_game(&players);
+    game.team_mode = crate::TeamMode::Partners as u8;
+    let home = game.track_length;
+    // Seat 0 has one piece left to bring home
+    game.home_counts[0] = 3;
+    game.positions[0] = [home - 2, home, home, home];
+    game.dice_roll = 2;
+    let move_piece = |game: &GameState| {
+        let mut accounts = vec![
+            TestAccount::zero_copy(Pubkey::new_unique(), game, game.total_bet),
+            TestAccount::signer(players[0]),
+            TestAccount::program(crate::ID), // session
+            TestAccount::program(crate::ID), // history
+            TestAccount::program(crate::ID), // profile
+        ];
+        run(
+            &mut accounts,
+            &crate::instruction::MovePiece { piece_index: 0 }.data(),
+        )
+        .unwrap();
+        accounts[0].game()
+    };
+
+    // Finishing alone wins nothing while the partner across the table plays on
+    let unfinished = move_piece(&game);
+    assert!(unfinished.status() == GameStatus::Active);
+    assert_eq!(unfinished.winner(), None);
+    assert_eq!(unfinished.turn, 1);
+
+    // Once the partner is home too, the partnership wins
+    game.home_counts[2] = 4;
+    game.positions[2] = [home; 4];
+    let won = move_piece(&game);
+    assert!(won.status() == GameStatus::Completed);
+    assert_eq!(won.winner(), Some(players[0]));
+    assert_eq!(won.second_place(), Some(players[2]));
+
+    // The pot after the platform fee is shared between the partners
+    let treasury = Pubkey::new_unique();
+    let mut accounts = distribute_accounts(&won, treasury);
+    run(
+        &mut accounts,
+        &crate::instruction::DistributePrizes {}.data(),
+    )
+    .unwrap();
+    assert_eq!(accounts[2].lamports, 40_000_000);
+    assert_eq!(accounts[3].lamports, 180_000_000);
+    assert_eq!(accounts[4].lamports, 180_000_000);
+}
+
+fn session_address(game: Pubkey, player: Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"session".as_ref(), game.as_ref(), player.as_ref()],
+        &crate::ID,
+    )
+}
+
+/// A session letting `signer` act for `player` in `game` until `expires_at`
+fn session_account(game: Pubkey, player: Pubkey, signer: Pubkey, expires_at: i64) -> TestAccount {
+    let (key, bump) = session_address(game, player);
+    let session = Session {
+        player,
+        game,
+        signer,
+        expires_at,
+        max_spend: 0,
+        bump,
+    };
+    TestAccount::borsh(key, &session, Session::INIT_SPACE)
+}
+
+#[test]
+fn create_session_authorizes_a_key_for_one_game() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let game = timed_game(&players);
+    let game_key = Pubkey::new_unique();
+    let session_signer = Pubkey::new_unique();
+    let create = |signer: Pubkey, expires_at: i64| {
+        let mut accounts = vec![
+            TestAccount::wallet(session_address(game_key, players[0]).0),
+            TestAccount::zero_copy(game_key, &game, game.total_bet),
+            TestAccount::signer(players[0]),
+            TestAccount::wallet(signer),
+            TestAccount::program(system_program::ID),
+        ];
+        let data = crate::instruction::CreateSession {
+            expires_at,
+            max_spend: 5_000_000,
+        }
+        .data();
+        run(&mut accounts, &data).map(|()| accounts)
+    };
+
+    // Sessions last at most a day and can't hand the player's own key to itself
+    for expires_at in [NOW, NOW + MAX_SESSION_SECONDS + 1] {
+        assert_eq!(
+            create(session_signer, expires_at).err(),
+            Some(error(ErrorCode::InvalidSessionExpiry))
+        );
+    }
+    assert_eq!(
+        create(players[0], NOW + 3_600).err(),
+        Some(error(ErrorCode::InvalidSession))
+    );
+
+    let accounts = create(session_signer, NOW + 3_600).unwrap();
+    let session = Session::try_deserialize(&mut accounts[0].data.as_slice()).unwrap();
+    assert_eq!(
+        (session.player, session.game, session.signer),
+        (players[0], game_key, session_signer)
+    );
+    assert_eq!(session.expires_at, NOW + 3_600);
+    assert_eq!(accounts[0].owner, crate::ID);
+    // The session key is funded for its transaction fees
+    assert_eq!(accounts[3].lamports, 5_000_000);
+}
+
+/// Run `request_roll` for the seat holding the turn, signed by `signer` on the
+/// strength of `session`
+fn request_roll_with_session(
+    game: &GameState,
+    game_key: Pubkey,
+    signer: Pubkey,
+    session: TestAccount,
+) -> ProgramResult {
+    let mut accounts = vec![
+        TestAccount::zero_copy(game_key, game, game.total_bet),
+        TestAccount::signer(signer),
+        session,
+        TestAccount::program(crate::ID), // history
+        TestAccount::program(system_program::ID),
+    ];
+    run(&mut accounts, &crate::instruction::RequestRoll {}.data())
+}
+
+#[test]
+fn session_keys_take_turns_until_they_expire() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let game = timed_game(&players);
+    let game_key = Pubkey::new_unique();
+    let signer = Pubkey::new_unique();
+    let session = || session_account(game_key, players[0], signer, NOW + 3_600);
+
+    request_roll_with_session(&game, game_key, signer, session()).unwrap();
+
+    warp(1, 3_600);
+    assert_eq!(
+        request_roll_with_session(&game, game_key, signer, session()).unwrap_err(),
+        error(ErrorCode::SessionExpired)
+    );
+}
+
+#[test]
+fn sessions_only_act_for_their_own_signer_and_game() {
+    let players = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let game = timed_game(&players);
+    let game_key = Pubkey::new_unique();
+    let signer = Pubkey::new_unique();
+
+    let session = session_account(game_key, players[0], signer, NOW + 3_600);
+    assert_eq!(
+        request_roll_with_session(&game, game_key, Pubkey::new_unique(), session).unwrap_err(),
+        error(ErrorCode::InvalidSession)
+    );
+
+    let other_game = session_account(Pubkey::new_unique(), players[0], signer, NOW + 3_600);
+    assert_eq!(
+        request_roll_with_session(&game, game_key, signer, other_game).unwrap_err(),
+        error(ErrorCode::InvalidSession)
+    );
+}
+
+#[test]
+fn revoke_session_closes_the_session_and_returns_its_rent() {
+    let player = Pubkey::new_unique();
+    let game_key = Pubkey::new_unique();
+    let session = session_account(game_key, player, Pubkey::new_unique(), NOW + 3_600);
+    let rent = session.lamports;
+
+    // Only the player the session acts for can revoke it
+    let stranger = Pubkey::new_unique();
+    let mut accounts = vec![
+        session_account(game_key, player, Pubkey::new_unique(), NOW + 3_600),
+        TestAccount::signer(stranger),
+    ];
+    let data = crate::instruction::RevokeSession {}.data();
+    assert!(run(&mut accounts, &data).is_err());
+
+    let mut accounts = vec![session, TestAccount::signer(player)];
+    let player_lamports = accounts[1].lamports;
+    run(&mut accounts, &data).unwrap();
+    assert_eq!(accounts[0].lamports, 0);
+    assert!(accounts[0].data.is_empty());
+    assert_eq!(accounts[0].owner, system_program::ID);
+    assert_eq!(accounts[1].lamports, player_lamports + rent);
+}
@@ -0,0 +1,78 @@
+//! Multi-player Elo ratings in fixed-point integer math.
+//!
+//! Like the rules engine this only uses `core`, and every step is integer
+//! arithmetic, so a rating computed on-chain can be reproduced exactly off-chain.
+//! A finished game is scored as a round of head-to-head games between every pair
+//! of players, decided by finishing order.
+
+use crate::rules::MAX_SEATS;
+
+/// Fixed-point scale of a stored rating, which is kept in hundredths of a point
+pub const RATING_SCALE: i64 = 100;
+/// Rating a new profile starts at (1500 points)
+pub const INITIAL_RATING: u32 = 1_500 * RATING_SCALE as u32;
+/// Most rating a player can win or lose in one game
+pub const K_FACTOR: i64 = 32 * RATING_SCALE;
+/// Fixed-point one for scores: a win is `SCORE_ONE`, a tie half of it
+pub const SCORE_ONE: i64 = 10_000;
+
+/// Rating gap between entries of `EXPECTED_SCORES`
+const EXPECTED_STEP: i64 = 25 * RATING_SCALE;
+/// Expected score `1 / (1 + 10^(-gap / 400))` of the stronger player for every
+/// 25 points of rating gap from 0 to 800, beyond which it is held flat
+const EXPECTED_SCORES: [i64; 33] = [
+    5000, 5359, 5715, 6063, 6401, 6725, 7034, 7325, 7597, 7850, 8083, 8296, 8490, 8666, 8823,
+    8965, 9091, 9203, 9302, 9390, 9468, 9536, 9595, 9648, 9693, 9733, 9768, 9799, 9825, 9848,
+    9868, 9886, 9901,
+];
+
+/// Expected score against an opponent rated `diff` below the player (negative if
+/// above), interpolated linearly between table entries
+pub fn expected_score(diff: i64) -> i64 {
+    let last = EXPECTED_SCORES.len() - 1;
+    let gap = diff.abs().min(last as i64 * EXPECTED_STEP);
+    let index = (gap / EXPECTED_STEP) as usize;
+    let expected = if index == last {
+        EXPECTED_SCORES[last]
+    } else {
+        let (low, high) = (EXPECTED_SCORES[index], EXPECTED_SCORES[index + 1]);
+        low + (high - low) * (gap % EXPECTED_STEP) / EXPECTED_STEP
+    };
+
+    if diff >= 0 {
+        expected
+    } else {
+        SCORE_ONE - expected
+    }
+}
+
+/// New ratings after a finished game, one per seat.
+///
+/// `ranks` holds each seat's finishing rank, lower is better and equal ranks tie.
+/// Each player's rating moves by `K_FACTOR` times their total score minus expected
+/// score over every pairing, divided by the number of opponents, so a two-player
+/// game is plain Elo.
+pub fn rate_game(ratings: &[u32], ranks: &[u8]) -> [u32; MAX_SEATS] {
+    let players = ratings.len().min(ranks.len()).min(MAX_SEATS);
+    let mut rated = [0; MAX_SEATS];
+    rated[..players].copy_from_slice(&ratings[..players]);
+    if players < 2 {
+        return rated;
+    }
+
+    for seat in 0..players {
+        let mut surplus = 0;
+        for opp in (0..players).filter(|opp| *opp != seat) {
+            let score = match ranks[seat].cmp(&ranks[opp]) {
+                core::cmp::Ordering::Less => SCORE_ONE,
+                core::cmp::Ordering::Equal => SCORE_ONE / 2,
+                core::cmp::Ordering::Greater => 0,
+            };
+            surplus += score - expected_score(ratings[seat] as i64 - ratings[opp] as i64);
+        }
+
+        let delta = K_FACTOR * surplus / (SCORE_ONE * (players as i64 - 1));
+        rated[seat] = (ratings[seat] as i64 + delta).max(0) as u32;
+    }
+    rated
+}
@@ -0,0 +1,825 @@
+//! Pure Ludo rules engine.
+//!
+//! Movement, home entry, captures and legal-move generation live here as plain
+//! data and functions over `core` only, so the same code backs the on-chain
+//! program and off-chain bots or the app (built natively or to WASM) without
+//! pulling in anchor-lang. The program copies its `GameState` into a [`Board`],
+//! validates and applies moves through it, then writes the positions back.
+
+#![no_std]
+
+/// Pieces each seat plays with
+pub const PIECES_PER_PLAYER: usize = 4;
+/// Most seats a board can hold
+pub const MAX_SEATS: usize = 6;
+/// Most safe squares a board can mark
+pub const MAX_SAFE_SQUARES: usize = 12;
+/// Squares on the shared track of the classic four-arm cross board
+pub const CROSS_TRACK_SQUARES: u8 = 52;
+/// Squares on the shared track of the six-arm hexagonal board
+pub const HEX_TRACK_SQUARES: u8 = 78;
+/// Position of a piece still in its base
+pub const BASE: u8 = 0;
+/// Position a piece enters the track at, its seat's start square
+pub const START: u8 = 1;
+/// Squares in the home column, the last one being the finish
+pub const HOME_STRETCH: u8 = 6;
+/// Most pieces a single move can capture
+pub const MAX_CAPTURES: usize = (MAX_SEATS - 1) * PIECES_PER_PLAYER;
+
+/// Stars of the cross board, eight squares after the start square of every arm.
+/// Start squares are only safe under the `safe_start_squares` house rule.
+const CROSS_SAFE_SQUARES: [u8; MAX_SAFE_SQUARES] = [8, 21, 34, 47, 0, 0, 0, 0, 0, 0, 0, 0];
+/// The same stars continued over all six arms of the hexagonal board
+const HEX_SAFE_SQUARES: [u8; MAX_SAFE_SQUARES] = [8, 21, 34, 47, 60, 73, 0, 0, 0, 0, 0, 0];
+
+/// Geometry and payout shape of a board for a given number of seats
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BoardLayout {
+    pub seats: u8,
+    pub track_squares: u8,
+    /// Square on the shared track each seat's `START` position sits on
+    pub start_offsets: [u8; MAX_SEATS],
+    pub safe_squares: [u8; MAX_SAFE_SQUARES],
+    pub safe_count: u8,
+    /// Finishing places that share the pot
+    pub paid_places: u8,
+}
+
+impl BoardLayout {
+    /// Layout for a table of `seats` players, `None` if no board seats that many.
+    ///
+    /// Two and three players use the cross board, two players sitting opposite
+    /// each other and three taking three of the four arms. Four players fill the
+    /// cross board and six the hexagonal one; those larger tables pay two places.
+    pub fn for_seats(seats: u8) -> Option<BoardLayout> {
+        let (track_squares, start_offsets, safe_squares, safe_count, paid_places) = match seats {
+            2 => (CROSS_TRACK_SQUARES, [0, 26, 0, 0, 0, 0], CROSS_SAFE_SQUARES, 4, 1),
+            3 => (CROSS_TRACK_SQUARES, [0, 13, 26, 0, 0, 0], CROSS_SAFE_SQUARES, 4, 1),
+            4 => (CROSS_TRACK_SQUARES, [0, 13, 26, 39, 0, 0], CROSS_SAFE_SQUARES, 4, 2),
+            6 => (HEX_TRACK_SQUARES, [0, 13, 26, 39, 52, 65], HEX_SAFE_SQUARES, 6, 2),
+            _ => return None,
+        };
+
+        Some(BoardLayout {
+            seats,
+            track_squares,
+            start_offsets,
+            safe_squares,
+            safe_count,
+            paid_places,
+        })
+    }
+
+    /// Position of a piece that has reached home
+    pub fn finish(&self) -> u8 {
+        finish_position(self.track_squares)
+    }
+}
+
+/// Furthest position on the shared track, the square before the home column.
+/// A piece goes round from its start square to two squares short of it, where its
+/// home column branches off, so it never lands on the square just before its start.
+pub fn last_track_position(track_squares: u8) -> u8 {
+    track_squares - 1
+}
+
+/// Position of a piece that has reached home on a track of `track_squares`
+pub fn finish_position(track_squares: u8) -> u8 {
+    last_track_position(track_squares) + HOME_STRETCH
+}
+
+/// House rules the engine validates moves against
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rules {
+    pub leave_base_on_one: bool,
+    pub extra_turn_on_six: bool,
+    pub extra_turn_on_capture: bool,
+    pub extra_turn_on_home: bool,
+    pub exact_roll_to_finish: bool,
+    pub safe_start_squares: bool,
+    pub blockades: bool,
+}
+
+impl Rules {
+    pub fn can_leave_base(&self, dice_value: u8) -> bool {
+        dice_value == 6 || (self.leave_base_on_one && dice_value == 1)
+    }
+}
+
+/// Why a move was rejected
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveError {
+    /// Seat or piece index is out of range, or the seat is no longer playing
+    InvalidPiece,
+    /// Piece is in base and the roll does not let it out
+    CannotStart,
+    /// Roll would carry the piece past home or off the track
+    InvalidMove,
+    /// An opponent's blockade stands on or before the landing square
+    Blocked,
+}
+
+/// A single legal move for one piece
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Move {
+    pub piece: u8,
+    pub from: u8,
+    pub to: u8,
+}
+
+/// An opponent piece sent back to base
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capture {
+    pub seat: u8,
+    pub piece: u8,
+}
+
+/// Everything that happened as the result of applying a move
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MoveOutcome {
+    pub mv: Move,
+    pub reached_home: bool,
+    /// Every piece of the moving seat is now home
+    pub finished: bool,
+    /// The moving seat rolls again
+    pub extra_turn: bool,
+    captures: [Capture; MAX_CAPTURES],
+    capture_count: u8,
+}
+
+impl MoveOutcome {
+    pub fn captures(&self) -> &[Capture] {
+        &self.captures[..self.capture_count as usize]
+    }
+}
+
+/// The legal moves for a roll, at most one per piece
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LegalMoves {
+    moves: [Move; PIECES_PER_PLAYER],
+    len: u8,
+}
+
+impl LegalMoves {
+    pub fn as_slice(&self) -> &[Move] {
+        &self.moves[..self.len as usize]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn contains_piece(&self, piece: u8) -> bool {
+        self.as_slice().iter().any(|mv| mv.piece == piece)
+    }
+}
+
+/// Snapshot of every piece on the board together with the board geometry.
+///
+/// Positions are each seat's progress: `BASE` in base, `START` up to the last track
+/// position on the shared track, then the home column up to the finish. Progress maps to a
+/// square on the shared track through the seat's start offset, so every seat's
+/// home column branches off two squares before its start square.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Board {
+    pub seats: u8,
+    pub positions: [[u8; PIECES_PER_PLAYER]; MAX_SEATS],
+    /// Seats still in the game; pieces of removed seats can't be captured
+    pub active: [bool; MAX_SEATS],
+    pub track_squares: u8,
+    /// Square on the shared track each seat's `START` position sits on
+    pub start_offsets: [u8; MAX_SEATS],
+    pub safe_squares: [u8; MAX_SAFE_SQUARES],
+    pub safe_count: u8,
+    pub rules: Rules,
+    /// Opposite seats play as partners and never capture each other
+    pub teams: bool,
+}
+
+impl Board {
+    pub fn last_track_position(&self) -> u8 {
+        last_track_position(self.track_squares)
+    }
+
+    pub fn finish(&self) -> u8 {
+        finish_position(self.track_squares)
+    }
+
+    /// Square on the shared track a seat's position maps to, `None` in base or
+    /// in the home column
+    pub fn absolute_square(&self, seat: u8, position: u8) -> Option<u8> {
+        if position < START || position > self.last_track_position() {
+            return None;
+        }
+        Some((self.start_offsets[seat as usize] + position - START) % self.track_squares)
+    }
+
+    pub fn are_partners(&self, seat: u8, other: u8) -> bool {
+        let half = self.seats / 2;
+        self.teams && seat != other && seat % half == other % half
+    }
+
+    /// Pieces on a safe square, or a protected start square, can't be captured
+    pub fn is_safe_square(&self, square: u8) -> bool {
+        if self.safe_squares[..self.safe_count as usize].contains(&square) {
+            return true;
+        }
+        self.rules.safe_start_squares && self.start_offsets[..self.seats as usize].contains(&square)
+    }
+
+    pub fn is_finished(&self, seat: u8) -> bool {
+        let finish = self.finish();
+        self.positions[seat as usize]
+            .iter()
+            .all(|position| *position == finish)
+    }
+
+    pub fn home_count(&self, seat: u8) -> u8 {
+        let finish = self.finish();
+        self.positions[seat as usize]
+            .iter()
+            .filter(|position| **position == finish)
+            .count() as u8
+    }
+
+    /// Two or more pieces of one opponent of `seat` hold `square`. A seat's own
+    /// pieces and its partner's never block it.
+    pub fn is_blockade(&self, seat: u8, square: u8) -> bool {
+        (0..self.seats)
+            .filter(|other| {
+                *other != seat && self.active[*other as usize] && !self.are_partners(seat, *other)
+            })
+            .any(|other| {
+                self.positions[other as usize]
+                    .iter()
+                    .filter(|position| self.absolute_square(other, **position) == Some(square))
+                    .count()
+                    >= 2
+            })
+    }
+
+    /// Work out where `piece` would land with `dice_value`, without moving it
+    pub fn check_move(&self, seat: u8, piece: u8, dice_value: u8) -> Result<Move, MoveError> {
+        if seat >= self.seats
+            || !self.active[seat as usize]
+            || piece as usize >= PIECES_PER_PLAYER
+        {
+            return Err(MoveError::InvalidPiece);
+        }
+
+        let from = self.positions[seat as usize][piece as usize];
+        let to = if from == BASE {
+            // Can only move piece out of base with a 6 (or a 1 if allowed)
+            if !self.rules.can_leave_base(dice_value) {
+                return Err(MoveError::CannotStart);
+            }
+            START
+        } else {
+            let finish = self.finish();
+            if from == finish {
+                return Err(MoveError::InvalidMove);
+            }
+
+            // The home column continues straight on from the shared track
+            let mut to = from + dice_value;
+            if to > finish {
+                // Overshooting home is invalid unless the table lets any roll finish
+                if self.rules.exact_roll_to_finish {
+                    return Err(MoveError::InvalidMove);
+                }
+                to = finish;
+            }
+            to
+        };
+
+        // A blockade can be neither passed nor landed on
+        if self.rules.blockades {
+            let first = if from == BASE { START } else { from + 1 };
+            let blocked = (first..=to).any(|position| {
+                self.absolute_square(seat, position)
+                    .is_some_and(|square| self.is_blockade(seat, square))
+            });
+            if blocked {
+                return Err(MoveError::Blocked);
+            }
+        }
+
+        Ok(Move { piece, from, to })
+    }
+
+    pub fn legal_moves(&self, seat: u8, dice_value: u8) -> LegalMoves {
+        let mut legal = LegalMoves::default();
+        for piece in 0..PIECES_PER_PLAYER as u8 {
+            if let Ok(mv) = self.check_move(seat, piece, dice_value) {
+                legal.moves[legal.len as usize] = mv;
+                legal.len += 1;
+            }
+        }
+        legal
+    }
+
+    /// Move `piece` and resolve captures, home arrival and extra turns
+    pub fn apply_move(
+        &mut self,
+        seat: u8,
+        piece: u8,
+        dice_value: u8,
+    ) -> Result<MoveOutcome, MoveError> {
+        let mv = self.check_move(seat, piece, dice_value)?;
+        self.positions[seat as usize][piece as usize] = mv.to;
+
+        let mut outcome = MoveOutcome {
+            mv,
+            ..MoveOutcome::default()
+        };
+
+        // Send every opponent piece on the landing square back to base
+        if let Some(square) = self.absolute_square(seat, mv.to) {
+            if !self.is_safe_square(square) {
+                for opp in 0..self.seats {
+                    if opp == seat || !self.active[opp as usize] || self.are_partners(seat, opp) {
+                        continue;
+                    }
+                    for opp_piece in 0..PIECES_PER_PLAYER as u8 {
+                        let opp_pos = self.positions[opp as usize][opp_piece as usize];
+                        // Only pieces on the shared track can be captured
+                        if self.absolute_square(opp, opp_pos) == Some(square) {
+                            self.positions[opp as usize][opp_piece as usize] = BASE;
+                            outcome.captures[outcome.capture_count as usize] = Capture {
+                                seat: opp,
+                                piece: opp_piece,
+                            };
+                            outcome.capture_count += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        outcome.reached_home = mv.to == self.finish();
+        outcome.finished = self.is_finished(seat);
+        outcome.extra_turn = !outcome.finished
+            && ((dice_value == 6 && self.rules.extra_turn_on_six)
+                || (outcome.capture_count > 0 && self.rules.extra_turn_on_capture)
+                || (outcome.reached_home && self.rules.extra_turn_on_home));
+
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUPPORTED_SEATS: [u8; 4] = [2, 3, 4, 6];
+
+    /// Classic rules without blockades, so single pieces move freely
+    fn classic() -> Rules {
+        Rules {
+            extra_turn_on_six: true,
+            exact_roll_to_finish: true,
+            ..Rules::default()
+        }
+    }
+
+    fn empty_board(seats: u8, rules: Rules) -> Board {
+        let layout = BoardLayout::for_seats(seats).unwrap();
+        let mut active = [false; MAX_SEATS];
+        active[..seats as usize].fill(true);
+        Board {
+            seats,
+            positions: [[BASE; PIECES_PER_PLAYER]; MAX_SEATS],
+            active,
+            track_squares: layout.track_squares,
+            start_offsets: layout.start_offsets,
+            safe_squares: layout.safe_squares,
+            safe_count: layout.safe_count,
+            rules,
+            teams: false,
+        }
+    }
+
+    /// Position of `seat`'s piece that sits on shared track `square`
+    fn position_on(board: &Board, seat: u8, square: u8) -> u8 {
+        (START..=board.last_track_position())
+            .find(|position| board.absolute_square(seat, *position) == Some(square))
+            .unwrap()
+    }
+
+    #[test]
+    fn absolute_square_maps_every_seat_from_its_start_square() {
+        // (seats, seat, position, square on the shared track)
+        let table: &[(u8, u8, u8, Option<u8>)] = &[
+            (2, 0, BASE, None),
+            (2, 0, START, Some(0)),
+            (2, 0, 51, Some(50)),
+            (2, 0, 52, None),
+            (2, 1, START, Some(26)),
+            (2, 1, 26, Some(51)),
+            (2, 1, 27, Some(0)),
+            (2, 1, 51, Some(24)),
+            (3, 2, START, Some(26)),
+            (3, 2, 51, Some(24)),
+            (4, 0, START, Some(0)),
+            (4, 0, 9, Some(8)),
+            (4, 0, 51, Some(50)),
+            (4, 1, START, Some(13)),
+            (4, 1, 39, Some(51)),
+            (4, 1, 40, Some(0)),
+            (4, 1, 51, Some(11)),
+            (4, 2, START, Some(26)),
+            (4, 2, 51, Some(24)),
+            (4, 3, START, Some(39)),
+            (4, 3, 14, Some(0)),
+            (4, 3, 51, Some(37)),
+            (4, 3, 57, None),
+            (6, 0, START, Some(0)),
+            (6, 0, 77, Some(76)),
+            (6, 0, 78, None),
+            (6, 1, START, Some(13)),
+            (6, 1, 77, Some(11)),
+            (6, 4, START, Some(52)),
+            (6, 4, 27, Some(0)),
+            (6, 4, 77, Some(50)),
+            (6, 5, START, Some(65)),
+            (6, 5, 14, Some(0)),
+            (6, 5, 77, Some(63)),
+            (6, 5, 83, None),
+        ];
+        for &(seats, seat, position, square) in table {
+            let board = empty_board(seats, classic());
+            assert_eq!(
+                board.absolute_square(seat, position),
+                square,
+                "seats {seats} seat {seat} position {position}"
+            );
+        }
+    }
+
+    #[test]
+    fn home_column_branches_off_two_squares_before_the_start_square() {
+        for seats in SUPPORTED_SEATS {
+            let board = empty_board(seats, classic());
+            let track = board.track_squares;
+            for seat in 0..seats {
+                let start = board.start_offsets[seat as usize];
+                let last = board.absolute_square(seat, board.last_track_position());
+                assert_eq!(last, Some((start + track - 2) % track));
+
+                // The square just before the start square is never visited
+                let skipped = (start + track - 1) % track;
+                assert!((START..=board.last_track_position())
+                    .all(|position| board.absolute_square(seat, position) != Some(skipped)));
+                for position in board.last_track_position() + 1..=board.finish() {
+                    assert_eq!(board.absolute_square(seat, position), None);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn leaving_base_needs_a_six() {
+        let board = empty_board(4, classic());
+        for dice_value in 1..=5 {
+            assert!(board.legal_moves(0, dice_value).is_empty());
+            assert_eq!(
+                board.check_move(0, 0, dice_value),
+                Err(MoveError::CannotStart)
+            );
+        }
+
+        let legal = board.legal_moves(0, 6);
+        assert_eq!(legal.as_slice().len(), PIECES_PER_PLAYER);
+        for (piece, mv) in legal.as_slice().iter().enumerate() {
+            assert_eq!(
+                *mv,
+                Move {
+                    piece: piece as u8,
+                    from: BASE,
+                    to: START
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn leave_base_on_one_rule_also_frees_pieces_on_a_one() {
+        let board = empty_board(
+            4,
+            Rules {
+                leave_base_on_one: true,
+                ..classic()
+            },
+        );
+        assert_eq!(board.legal_moves(0, 1).as_slice().len(), PIECES_PER_PLAYER);
+        assert!(board.legal_moves(0, 2).is_empty());
+    }
+
+    #[test]
+    fn legal_moves_only_lists_pieces_that_can_move() {
+        let mut board = empty_board(4, classic());
+        board.positions[0] = [10, BASE, board.finish(), board.finish() - 2];
+
+        let legal = board.legal_moves(0, 3);
+        assert_eq!(
+            legal.as_slice(),
+            &[Move {
+                piece: 0,
+                from: 10,
+                to: 13
+            }]
+        );
+        assert!(legal.contains_piece(0));
+        assert!(!legal.contains_piece(3));
+
+        let legal = board.legal_moves(0, 2);
+        assert_eq!(
+            legal.as_slice(),
+            &[
+                Move {
+                    piece: 0,
+                    from: 10,
+                    to: 12
+                },
+                Move {
+                    piece: 3,
+                    from: board.finish() - 2,
+                    to: board.finish()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn legal_moves_is_empty_for_removed_or_unknown_seats() {
+        let mut board = empty_board(4, classic());
+        board.active[2] = false;
+        assert!(board.legal_moves(2, 6).is_empty());
+        assert!(board.legal_moves(4, 6).is_empty());
+        assert_eq!(
+            board.check_move(0, PIECES_PER_PLAYER as u8, 6),
+            Err(MoveError::InvalidPiece)
+        );
+    }
+
+    #[test]
+    fn overshooting_home_depends_on_exact_roll_rule() {
+        let mut exact = empty_board(4, classic());
+        exact.positions[0][0] = exact.finish() - 2;
+        assert_eq!(exact.check_move(0, 0, 3), Err(MoveError::InvalidMove));
+
+        let mut any_roll = exact;
+        any_roll.rules.exact_roll_to_finish = false;
+        let outcome = any_roll.apply_move(0, 0, 3).unwrap();
+        assert_eq!(outcome.mv.to, any_roll.finish());
+        assert!(outcome.reached_home);
+    }
+
+    #[test]
+    fn every_position_and_roll_moves_as_expected() {
+        for seats in SUPPORTED_SEATS {
+            for seat in 0..seats {
+                let empty = empty_board(seats, classic());
+                let finish = empty.finish();
+                for from in BASE..=finish {
+                    for dice_value in 1..=6 {
+                        let mut board = empty;
+                        board.positions[seat as usize][0] = from;
+
+                        let expected = if from == BASE {
+                            (dice_value == 6).then_some(START)
+                        } else if from + dice_value > finish {
+                            None
+                        } else {
+                            Some(from + dice_value)
+                        };
+                        assert_eq!(
+                            board.legal_moves(seat, dice_value).contains_piece(0),
+                            expected.is_some(),
+                            "seats {seats} seat {seat} from {from} roll {dice_value}"
+                        );
+
+                        match board.apply_move(seat, 0, dice_value) {
+                            Ok(outcome) => {
+                                let to = expected.unwrap();
+                                assert_eq!(outcome.mv, Move { piece: 0, from, to });
+                                assert_eq!(board.positions[seat as usize][0], to);
+                                assert_eq!(outcome.reached_home, to == finish);
+                                assert!(outcome.captures().is_empty());
+                                assert!(!outcome.finished);
+                            }
+                            Err(_) => {
+                                assert!(expected.is_none());
+                                assert_eq!(board, {
+                                    let mut unchanged = empty;
+                                    unchanged.positions[seat as usize][0] = from;
+                                    unchanged
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn landing_on_an_opponent_sends_it_back_to_base() {
+        let mut board = empty_board(4, classic());
+        board.positions[0][0] = 3;
+        let square = board.absolute_square(0, 6).unwrap();
+        board.positions[1][2] = position_on(&board, 1, square);
+
+        let outcome = board.apply_move(0, 0, 3).unwrap();
+        assert_eq!(outcome.captures(), &[Capture { seat: 1, piece: 2 }]);
+        assert_eq!(board.positions[1][2], BASE);
+        assert_eq!(board.positions[0][0], 6);
+        assert!(!outcome.extra_turn);
+
+        let mut capture_bonus = empty_board(
+            4,
+            Rules {
+                extra_turn_on_capture: true,
+                ..classic()
+            },
+        );
+        capture_bonus.positions[0][0] = 3;
+        capture_bonus.positions[1][2] = position_on(&capture_bonus, 1, square);
+        assert!(capture_bonus.apply_move(0, 0, 3).unwrap().extra_turn);
+    }
+
+    #[test]
+    fn landing_captures_every_opponent_piece_on_the_square() {
+        let mut board = empty_board(6, classic());
+        board.positions[0][0] = 3;
+        let square = board.absolute_square(0, 6).unwrap();
+        board.positions[2][0] = position_on(&board, 2, square);
+        board.positions[2][3] = position_on(&board, 2, square);
+        board.positions[4][1] = position_on(&board, 4, square);
+
+        let outcome = board.apply_move(0, 0, 3).unwrap();
+        assert_eq!(
+            outcome.captures(),
+            &[
+                Capture { seat: 2, piece: 0 },
+                Capture { seat: 2, piece: 3 },
+                Capture { seat: 4, piece: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn safe_squares_partners_and_removed_seats_are_not_captured() {
+        let star = CROSS_SAFE_SQUARES[1];
+        let mut board = empty_board(4, classic());
+        board.positions[0][0] = position_on(&board, 0, star) - 2;
+        board.positions[1][0] = position_on(&board, 1, star);
+        assert!(board.apply_move(0, 0, 2).unwrap().captures().is_empty());
+        assert_ne!(board.positions[1][0], BASE);
+
+        let mut teams = empty_board(4, classic());
+        teams.teams = true;
+        teams.positions[0][0] = 3;
+        let square = teams.absolute_square(0, 6).unwrap();
+        teams.positions[2][0] = position_on(&teams, 2, square);
+        assert!(teams.apply_move(0, 0, 3).unwrap().captures().is_empty());
+
+        let mut removed = empty_board(4, classic());
+        removed.positions[0][0] = 3;
+        removed.positions[3][0] = position_on(&removed, 3, square);
+        removed.active[3] = false;
+        assert!(removed.apply_move(0, 0, 3).unwrap().captures().is_empty());
+    }
+
+    #[test]
+    fn start_squares_are_only_safe_under_the_house_rule() {
+        for seats in SUPPORTED_SEATS {
+            for protected in [false, true] {
+                let rules = Rules {
+                    safe_start_squares: protected,
+                    ..classic()
+                };
+                let board = empty_board(seats, rules);
+                for seat in 0..seats {
+                    let start = board.start_offsets[seat as usize];
+                    assert_eq!(board.is_safe_square(start), protected);
+
+                    // An opponent sitting on the start square is captured without the rule
+                    let mut board = board;
+                    let mover = (seat + 1) % seats;
+                    let landing = position_on(&board, mover, start);
+                    board.positions[mover as usize][0] = landing - 1;
+                    board.positions[seat as usize][0] = START;
+                    let outcome = board.apply_move(mover, 0, 1).unwrap();
+                    assert_eq!(outcome.captures().is_empty(), protected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stars_are_safe_on_every_board() {
+        for seats in SUPPORTED_SEATS {
+            let board = empty_board(seats, classic());
+            let arms = board.track_squares / 13;
+            for arm in 0..arms {
+                assert!(board.is_safe_square(arm * 13 + 8));
+            }
+            assert_eq!(board.safe_count, arms);
+        }
+    }
+
+    #[test]
+    fn pieces_in_the_home_column_are_never_captured() {
+        let mut board = empty_board(4, classic());
+        board.positions[1][0] = board.last_track_position() + 1;
+        for from in START..board.last_track_position() {
+            let mut board = board;
+            board.positions[0][0] = from;
+            if let Ok(outcome) = board.apply_move(0, 0, 1) {
+                assert!(outcome.captures().is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn blockades_stop_opponents_but_not_partners() {
+        let rules = Rules {
+            blockades: true,
+            ..classic()
+        };
+        let mut board = empty_board(4, rules);
+        board.positions[0][0] = 3;
+        let square = board.absolute_square(0, 6).unwrap();
+        let blocking = position_on(&board, 2, square);
+        board.positions[2][0] = blocking;
+        board.positions[2][1] = blocking;
+
+        // Landing on or passing an opponent's pair is blocked
+        assert!(board.is_blockade(0, square));
+        assert_eq!(board.check_move(0, 0, 3), Err(MoveError::Blocked));
+        assert_eq!(board.check_move(0, 0, 5), Err(MoveError::Blocked));
+        assert!(board.check_move(0, 0, 2).is_ok());
+
+        // The same pair belongs to seat 0's partner in team mode
+        board.teams = true;
+        assert!(!board.is_blockade(0, square));
+        assert!(board.check_move(0, 0, 5).is_ok());
+        assert!(board.is_blockade(1, square));
+
+        // A seat's own pair never blocks it
+        let mut own = empty_board(4, rules);
+        own.positions[0] = [3, 6, 6, BASE];
+        assert!(!own.is_blockade(0, square));
+        assert!(own.check_move(0, 0, 5).is_ok());
+    }
+
+    #[test]
+    fn six_grants_another_turn_unless_the_seat_just_finished() {
+        let mut board = empty_board(4, classic());
+        board.positions[0] = [10, BASE, BASE, BASE];
+        assert!(board.apply_move(0, 0, 6).unwrap().extra_turn);
+        assert!(!board.apply_move(0, 0, 5).unwrap().extra_turn);
+
+        let finish = board.finish();
+        board.positions[0] = [finish - 6, finish, finish, finish];
+        let outcome = board.apply_move(0, 0, 6).unwrap();
+        assert!(outcome.reached_home && outcome.finished);
+        assert!(!outcome.extra_turn);
+    }
+
+    #[test]
+    fn reaching_home_grants_another_turn_with_the_home_rule() {
+        let mut board = empty_board(
+            4,
+            Rules {
+                extra_turn_on_home: true,
+                ..classic()
+            },
+        );
+        let finish = board.finish();
+        board.positions[0] = [finish - 2, BASE, BASE, BASE];
+        let outcome = board.apply_move(0, 0, 2).unwrap();
+        assert!(outcome.reached_home && outcome.extra_turn && !outcome.finished);
+    }
+
+    #[test]
+    fn is_finished_only_once_every_piece_is_home() {
+        let mut board = empty_board(4, classic());
+        let finish = board.finish();
+        assert!(!board.is_finished(0));
+        assert_eq!(board.home_count(0), 0);
+
+        for piece in 0..PIECES_PER_PLAYER {
+            board.positions[0][piece] = finish;
+            assert_eq!(board.home_count(0), piece as u8 + 1);
+            assert_eq!(board.is_finished(0), piece == PIECES_PER_PLAYER - 1);
+        }
+
+        board.positions[1] = [finish - 1, finish, finish, finish];
+        assert!(!board.is_finished(1));
+        assert_eq!(board.check_move(0, 0, 1), Err(MoveError::InvalidMove));
+        assert!(board.legal_moves(0, 6).is_empty());
+    }
+}
@@ -47,9 +47,12 @@ const BetSelectionScreen = ({ navigation }) => {
             const joinedGames = await fetchJoinedGames(wallet);
             const unfinished = joinedGames.find(g => g.status === 0 || g.status === 1);
             if (unfinished) {
+                // Keep the dice secret of the game this device created or joined
+                const saved = JSON.parse(await AsyncStorage.getItem('activeGame') || '{}');
                 await AsyncStorage.setItem('activeGame', JSON.stringify({
                     gameId: unfinished.id,
                     gameSeed: '',
+                    diceSecret: saved.gameId === unfinished.id ? saved.diceSecret : undefined,
                     creator: unfinished.isCreator
                 }));
                 playSound('game_start');
@@ -95,8 +98,8 @@ const BetSelectionScreen = ({ navigation }) => {
         setLoading(true);
         try {
             const betAmountLamports = parseFloat(betAmount) * 1e9;
-            const { txid, gameAccountPubkey, gameSeed } = await createGame(wallet, playerCount, betAmountLamports);
-            await AsyncStorage.setItem('activeGame', JSON.stringify({ gameId: gameAccountPubkey.toString(), gameSeed, creator: true }));
+            const { txid, gameAccountPubkey, gameSeed, diceSecret } = await createGame(wallet, playerCount, betAmountLamports);
+            await AsyncStorage.setItem('activeGame', JSON.stringify({ gameId: gameAccountPubkey.toString(), gameSeed, diceSecret, creator: true }));
             setCreateModalVisible(false);
             navigate('LudoBoardScreen', { gameId: gameAccountPubkey.toString(), gameSeed, creator: true });
             playSound('game_start');
@@ -112,8 +115,8 @@ const BetSelectionScreen = ({ navigation }) => {
         if (!wallet || !wallet.publicKey) return Alert.alert("Error", "Wallet not found.");
         setLoading(true);
         try {
-            const { txid, gameAccountPubkey } = await joinGame(wallet, gameId);
-            await AsyncStorage.setItem('activeGame', JSON.stringify({ gameId: gameAccountPubkey.toString(), creator: false }));
+            const { txid, gameAccountPubkey, diceSecret } = await joinGame(wallet, gameId);
+            await AsyncStorage.setItem('activeGame', JSON.stringify({ gameId: gameAccountPubkey.toString(), diceSecret, creator: false }));
             navigate('LudoBoardScreen', { gameId: gameAccountPubkey.toString(), creator: false });
             playSound('game_start');
         } catch (error) {
@@ -6,21 +6,202 @@ import {
     TransactionInstruction,
     Transaction,
     sendAndConfirmTransaction,
-    SYSVAR_RENT_PUBKEY
+    SYSVAR_RENT_PUBKEY,
+    SYSVAR_SLOT_HASHES_PUBKEY
 } from '@solana/web3.js';
 // Import Buffer from buffer shim for React Native
 import { Buffer } from 'buffer';
+// Hashing from the crypto shim, see shim.js
+import { createHash } from 'crypto';
 // Import the IDL
 import idl from '../idl/cryptoria_idl.json';
 
 // Constants
-const GAME_ACCOUNT_SIZE = 1500; // Reduced from 10240 to match actual program requirements
 const SOLANA_NETWORK = 'https://api.devnet.solana.com';
 const PROGRAM_ID = new PublicKey(idl.address);
 
+// Links in a player's dice hash chain, one revealed per roll
+const DICE_CHAIN_LENGTH = 1000;
+// Tables one lobby page lists, LOBBY_CAPACITY in the program
+const LOBBY_CAPACITY = 64;
+// RuleSet::STANDARD in the program's field order
+const STANDARD_RULES = [0, 1, 0, 0, 1, 0, 1, 1];
+// Seats a GameState has room for, MAX_SEATS in the program
+const MAX_SEATS = 6;
+// Account sizes, discriminator included, of a game and of its move log when created
+const GAME_ACCOUNT_SIZE = 8 + 936;
+const HISTORY_ACCOUNT_SIZE = 8 + 40 + 256 * 16;
+const LOBBY_ACCOUNT_SIZE = 8 + 46 + 4 + LOBBY_CAPACITY * 35;
+// Anchor account discriminators
+const GAME_STATE_DISCRIMINATOR = Buffer.from([144, 94, 208, 172, 248, 99, 134, 120]);
+const LOBBY_DISCRIMINATOR = Buffer.from([167, 194, 217, 163, 92, 92, 103, 49]);
+
 // Create a connection instance
 const getConnection = () => new Connection(SOLANA_NETWORK, 'confirmed');
 
+/**
+ * Find the first page of the lobby for this bet and table size with room for
+ * another table. Pages are created as the ones before them fill up.
+ */
+async function findLobbyPage(connection, maxPlayers, betAmountBytes) {
+    for (let lobbyPage = 0; ; lobbyPage++) {
+        const lobbyPageBytes = Buffer.alloc(4);
+        lobbyPageBytes.writeUInt32LE(lobbyPage);
+        const [lobbyPubkey] = PublicKey.findProgramAddressSync(
+            [
+                Buffer.from("lobby"),
+                PublicKey.default.toBuffer(), // SOL tables have no mint
+                betAmountBytes,
+                Buffer.from([maxPlayers]),
+                lobbyPageBytes
+            ],
+            PROGRAM_ID
+        );
+        
+        // Discriminator, mint, bet_amount, max_players, page and bump come before
+        // the length of the games list
+        const lobbyInfo = await connection.getAccountInfo(lobbyPubkey);
+        if (!lobbyInfo || lobbyInfo.data.readUInt32LE(8 + 32 + 8 + 1 + 4 + 1) < LOBBY_CAPACITY) {
+            return { lobbyPage, lobbyPubkey };
+        }
+    }
+}
+
+/**
+ * Decode a zero-copy GameState account. Offsets follow the field order of
+ * GameState in the program, after the 8-byte discriminator.
+ */
+function decodeGame(data) {
+    const buffer = Buffer.from(data);
+    const pubkeyAt = (offset) => new PublicKey(buffer.subarray(offset, offset + 32));
+    const seats = (offset) => Array.from({ length: MAX_SEATS }, (_, seat) => pubkeyAt(offset + seat * 32));
+    return {
+        version: buffer[8],
+        playerCount: buffer[9],
+        maxPlayers: buffer[10],
+        turn: buffer[12],
+        status: buffer[13],
+        diceRoll: buffer[14] || null,
+        betAmount: buffer.readBigUInt64LE(16),
+        lastMoveTime: Number(buffer.readBigInt64LE(48)),
+        randomnessRequestSlot: buffer.readBigUInt64LE(64),
+        creator: pubkeyAt(72),
+        players: seats(104),
+        depositedPlayers: seats(296),
+        oracle: pubkeyAt(552),
+        mint: pubkeyAt(584),
+        commitments: Array.from({ length: MAX_SEATS }, (_, seat) => buffer.subarray(616 + seat * 32, 648 + seat * 32)),
+        randomnessRequested: buffer[874] !== 0,
+        history: buffer[936] !== 0,
+        listed: buffer[937] !== 0
+    };
+}
+
+/**
+ * Start a new dice hash chain. The commitment is sent to the program and the
+ * secret kept on the device, each roll revealing the link before the last one.
+ */
+function newDiceChain() {
+    const diceSecret = Buffer.from(crypto.getRandomValues(new Uint8Array(32)));
+    let commitment = diceSecret;
+    for (let i = 0; i < DICE_CHAIN_LENGTH; i++) {
+        commitment = createHash('sha256').update(commitment).digest();
+    }
+    return { diceSecret: diceSecret.toString('hex'), commitment };
+}
+
+/**
+ * Walk the hash chain of `diceSecret` up to the link whose hash is the seat's
+ * current commitment. That link is the next reveal.
+ */
+function nextReveal(diceSecret, commitment) {
+    let link = Buffer.from(diceSecret, 'hex');
+    for (let i = 0; i < DICE_CHAIN_LENGTH; i++) {
+        const next = createHash('sha256').update(link).digest();
+        if (next.equals(Buffer.from(commitment))) {
+            return link;
+        }
+        link = next;
+    }
+    throw new Error("The stored dice secret does not match this game's commitment.");
+}
+
+/**
+ * What the game lists show of a decoded game
+ */
+function gameSummary(gamePubkey, game) {
+    return {
+        id: gamePubkey.toString(),
+        creator: game.creator.toString(),
+        betAmount: game.betAmount.toString(),
+        maxPlayers: game.maxPlayers,
+        currentPlayers: game.playerCount,
+        status: game.status
+    };
+}
+
+/**
+ * Program accounts of one type, picked out by size and discriminator
+ */
+async function fetchAccountsOfType(connection, size, discriminator) {
+    const accounts = await connection.getProgramAccounts(PROGRAM_ID, {
+        filters: [{ dataSize: size }]
+    });
+    return accounts.filter(({ account }) =>
+        Buffer.from(account.data.subarray(0, 8)).equals(discriminator)
+    );
+}
+
+/**
+ * Find the lobby page listing a game, or null if the game is not listed
+ */
+async function findGameLobby(connection, gamePubkey, game) {
+    if (!game.listed) {
+        return null;
+    }
+    const betAmountBytes = Buffer.alloc(8);
+    betAmountBytes.writeBigUInt64LE(game.betAmount);
+    for (let lobbyPage = 0; ; lobbyPage++) {
+        const lobbyPageBytes = Buffer.alloc(4);
+        lobbyPageBytes.writeUInt32LE(lobbyPage);
+        const [lobbyPubkey] = PublicKey.findProgramAddressSync(
+            [
+                Buffer.from("lobby"),
+                game.mint.toBuffer(),
+                betAmountBytes,
+                Buffer.from([game.maxPlayers]),
+                lobbyPageBytes
+            ],
+            PROGRAM_ID
+        );
+        const lobbyInfo = await connection.getAccountInfo(lobbyPubkey);
+        if (!lobbyInfo) {
+            throw new Error("No lobby page lists this game.");
+        }
+        if (decodeLobbyGames(lobbyInfo.data).some(entry => entry.game.equals(gamePubkey))) {
+            return lobbyPubkey;
+        }
+    }
+}
+
+/**
+ * Decode the games listed in a Lobby account, after its discriminator, mint,
+ * bet_amount, max_players, page and bump
+ */
+function decodeLobbyGames(data) {
+    const buffer = Buffer.from(data);
+    const count = buffer.readUInt32LE(54);
+    return Array.from({ length: count }, (_, index) => {
+        const offset = 58 + index * 35;
+        return {
+            game: new PublicKey(buffer.subarray(offset, offset + 32)),
+            playerCount: buffer[offset + 32],
+            rules: buffer[offset + 33],
+            teamMode: buffer[offset + 34]
+        };
+    });
+}
+
 /**
  * Create a new game with the specified parameters - using a simplified approach
  */
@@ -39,7 +220,8 @@ export async function createGame(wallet, maxPlayers, betAmount) {
         console.log(`Current wallet balance: ${balance / 1e9} SOL`);
         
         // Calculate how much we need for the transaction
-        const rentExemptBalance = await connection.getMinimumBalanceForRentExemption(GAME_ACCOUNT_SIZE);
+        const rentExemptBalance = await connection.getMinimumBalanceForRentExemption(GAME_ACCOUNT_SIZE)
+            + await connection.getMinimumBalanceForRentExemption(HISTORY_ACCOUNT_SIZE);
         const totalNeeded = rentExemptBalance + betAmount + 5000000; // Add some for transaction fees
         
         console.log(`Rent exemption needed: ${rentExemptBalance / 1e9} SOL`);
@@ -50,40 +232,84 @@ export async function createGame(wallet, maxPlayers, betAmount) {
             throw new Error(`Insufficient funds. You have ${balance / 1e9} SOL but need approximately ${totalNeeded / 1e9} SOL. Please add more funds to your wallet.`);
         }
         
-        // Generate a unique game seed
-        const gameSeed = `game_${Date.now()}_${Math.random().toString(36).substring(2, 15)}`;
-        console.log("Game seed:", gameSeed);
-        
-        // Find the PDA for the game account using the seed
-        const [gameAccountPubkey, bump] = await PublicKey.findProgramAddress(
-            [
-                Buffer.from("game"),
-                Buffer.from(gameSeed)
-            ],
+        // The game takes the next id from the global counter, which does not exist
+        // until the first game is created
+        const [gameCounterPubkey] = PublicKey.findProgramAddressSync(
+            [Buffer.from("game_counter")],
+            PROGRAM_ID
+        );
+        const counterInfo = await connection.getAccountInfo(gameCounterPubkey);
+        const gameId = counterInfo ? counterInfo.data.readBigUInt64LE(8) : 0n;
+        const gameSeed = gameId.toString();
+        console.log("Game id:", gameSeed);
+        
+        // Find the PDAs of the game and its move log
+        const gameIdBytes = Buffer.alloc(8);
+        gameIdBytes.writeBigUInt64LE(gameId);
+        const [gameAccountPubkey] = PublicKey.findProgramAddressSync(
+            [Buffer.from("game"), gameIdBytes],
+            PROGRAM_ID
+        );
+        const [historyPubkey] = PublicKey.findProgramAddressSync(
+            [Buffer.from("history"), gameAccountPubkey.toBuffer()],
+            PROGRAM_ID
+        );
+        const [profilePubkey] = PublicKey.findProgramAddressSync(
+            [Buffer.from("profile"), wallet.publicKey.toBuffer()],
+            PROGRAM_ID
+        );
+        const [configPubkey] = PublicKey.findProgramAddressSync(
+            [Buffer.from("config")],
             PROGRAM_ID
         );
         console.log("Game account PDA:", gameAccountPubkey.toString());
         
-        // Create the initialize_game instruction
+        const betAmountBytes = Buffer.alloc(8);
+        betAmountBytes.writeBigUInt64LE(BigInt(betAmount));
+        const { lobbyPage, lobbyPubkey } = await findLobbyPage(connection, maxPlayers, betAmountBytes);
+        
+        // Head of the creator's dice hash chain, revealed one link per roll
+        const { diceSecret, commitment } = newDiceChain();
+        
+        const lobbyPageBytes = Buffer.alloc(4);
+        lobbyPageBytes.writeUInt32LE(lobbyPage);
+        
+        // Optional accounts left out are passed as the program id
         const initializeGameIx = new TransactionInstruction({
             programId: PROGRAM_ID,
             keys: [
+                { pubkey: gameCounterPubkey, isSigner: false, isWritable: true },
                 { pubkey: gameAccountPubkey, isSigner: false, isWritable: true },
+                { pubkey: historyPubkey, isSigner: false, isWritable: true },
+                { pubkey: profilePubkey, isSigner: false, isWritable: true },
+                { pubkey: configPubkey, isSigner: false, isWritable: false },
                 { pubkey: wallet.publicKey, isSigner: true, isWritable: true },
+                { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // mint
+                { pubkey: lobbyPubkey, isSigner: false, isWritable: true },
+                { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // vault
+                { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // creator_token_account
+                { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // token_program
+                { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // associated_token_program
                 { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
                 { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false }
             ],
             data: Buffer.from([
-                // Anchor instruction discriminator for initialize_game (first 8 bytes of the hash of "initialize_game")
+                // Anchor instruction discriminator for initialize_game (first 8 bytes of the hash of "global:initialize_game")
                 44, 62, 102, 247, 126, 208, 130, 215,
                 // max_players (u8)
                 maxPlayers,
                 // bet_amount (u64) - 8 bytes
-                ...new Uint8Array(new BigUint64Array([BigInt(betAmount)]).buffer),
-                // Game seed length (u32) - 4 bytes
-                ...new Uint8Array(new Uint32Array([gameSeed.length]).buffer),
-                // Game seed bytes
-                ...new TextEncoder().encode(gameSeed)
+                ...betAmountBytes,
+                // lobby_page (u32) - 4 bytes
+                ...lobbyPageBytes,
+                // commitment ([u8; 32])
+                ...commitment,
+                // oracle (Option<Pubkey>) - None, rolls are commit-reveal
+                0,
+                // rules (RuleSet) - the standard rules, one bool per rule
+                ...STANDARD_RULES,
+                // team_mode (TeamMode) - Solo
+                0
             ])
         });
         
@@ -114,6 +340,7 @@ export async function createGame(wallet, maxPlayers, betAmount) {
             txid: signature,
             gameAccountPubkey,
             gameSeed,
+            diceSecret,
             maxPlayers,
             betAmount
         };
@@ -142,148 +369,34 @@ export async function createGame(wallet, maxPlayers, betAmount) {
 }
 
 /**
- * Fetch all available games that are waiting for players
- * Simplified for React Native compatibility
+ * Fetch all available games that are waiting for players, from the lobby pages
+ * of every bet and table size
  */
 export async function fetchAvailableGames() {
-    console.log("Fetching available games from blockchain");
+    console.log("Fetching available games from the lobbies");
     
     const connection = getConnection();
     
     try {
-        // Get all program accounts
-        const accounts = await connection.getProgramAccounts(PROGRAM_ID);
-        console.log(`Found ${accounts.length} program accounts`);
-        
-        if (accounts.length === 0) {
-            console.log("No program accounts found");
-            return [];
-        }
-        
-        // Add debug logging to see raw account data
-        accounts.forEach((account, index) => {
-            const accountData = account.account.data;
-            console.log(`Account ${index}: ${account.pubkey.toString()}`);
-            console.log(`Data length: ${accountData.length}`);
-            if (accountData.length >= 8) {
-                const discBytes = new Uint8Array(accountData.slice(0, 8));
-                console.log(`Discriminator: [${[...discBytes]}]`);
-            }
-        });
-        
-        // Parse each account to get game details
-        const games = await Promise.all(accounts.map(async (account, index) => {
-            try {
-                if (!account || !account.account || !account.account.data) {
-                    console.log(`Account ${index}: Invalid account data structure`);
-                    return null;
-                }
-
-                // Convert account data to Uint8Array if it's not already
-                const data = new Uint8Array(account.account.data);
-                
-                // Check if we have enough data for the discriminator
-                if (data.length < 8) {
-                    console.log(`Account ${index}: Data too short for discriminator`);
-                    return null;
+        const lobbies = await fetchAccountsOfType(connection, LOBBY_ACCOUNT_SIZE, LOBBY_DISCRIMINATOR);
+        const listed = lobbies.flatMap(({ account }) => decodeLobbyGames(account.data));
+        console.log(`Found ${listed.length} listed games in ${lobbies.length} lobby pages`);
+        
+        // The lobby only keeps what tables are filtered on; the rest comes from
+        // the games themselves, at most 100 per request
+        const games = [];
+        for (let start = 0; start < listed.length; start += 100) {
+            const keys = listed.slice(start, start + 100).map(entry => entry.game);
+            const infos = await connection.getMultipleAccountsInfo(keys);
+            infos.forEach((info, index) => {
+                if (info && info.data.length === GAME_ACCOUNT_SIZE) {
+                    games.push(gameSummary(keys[index], decodeGame(info.data)));
                 }
-                
-                // The account data starts with an 8-byte discriminator
-                const discriminator = data.slice(0, 8);
-                console.log(`Account ${index} discriminator: [${[...discriminator]}]`);
-                
-                // Try both discriminators since there might be a version mismatch
-                const gameStateDiscriminator1 = new Uint8Array([103, 195, 198, 221, 41, 211, 145, 83]);
-                const gameStateDiscriminator2 = new Uint8Array([144, 94, 208, 172, 248, 99, 134, 120]);
-                
-                // Check against both possible discriminators
-                const matches1 = discriminator.every((byte, i) => byte === gameStateDiscriminator1[i]);
-                const matches2 = discriminator.every((byte, i) => byte === gameStateDiscriminator2[i]);
-                
-                if (!matches1 && !matches2) {
-                    console.log(`Account ${index}: Discriminator does not match any known GameState discriminator`);
-                    return null;
-                }
-                
-                console.log(`Account ${index}: Found matching game state discriminator`);
-                
-                // Try to parse game state based on the Anchor layout
-                try {
-                    // Skip discriminator (8 bytes)
-                    let offset = 8;
-                    
-                    // Read creator (Pubkey) - 32 bytes
-                    const creatorBytes = data.slice(offset, offset + 32);
-                    const creator = new PublicKey(creatorBytes);
-                    offset += 32;
-                    
-                    // Read players array length (u32) - 4 bytes
-                    const playersLenBytes = data.slice(offset, offset + 4);
-                    const playersLen = new Uint32Array(playersLenBytes.buffer)[0];
-                    offset += 4;
-                    
-                    // Skip players array
-                    offset += playersLen * 32;
-                    
-                    // Skip deposited_players array (similar structure)
-                    const depositedPlayersLenBytes = data.slice(offset, offset + 4);
-                    const depositedPlayersLen = new Uint32Array(depositedPlayersLenBytes.buffer)[0];
-                    offset += 4;
-                    offset += depositedPlayersLen * 32;
-                    
-                    // Read max_players (u8)
-                    const maxPlayers = data[offset];
-                    offset += 1;
-                    
-                    // Read bet_amount (u64)
-                    const betAmountBytes = data.slice(offset, offset + 8);
-                    const betAmount = new BigUint64Array(betAmountBytes.buffer)[0];
-                    
-                    // We don't need to parse every field, just the crucial ones
-                    
-                    // Get the game status which should be at a fixed offset from the end
-                    // This is approximate since vector lengths can vary
-                    const gameStatusOffset = data.length - 100; // Rough estimate
-                    let gameStatus = 0; // Default to WaitingForPlayers
-                    
-                    // Try to find game status by checking a reasonable range
-                    for (let i = gameStatusOffset; i < data.length - 10; i++) {
-                        // Look for a byte that could be the game status (expecting 0, 1, 2, etc)
-                        if (data[i] <= 4 && data[i+1] === 0 && data[i+2] === 0 && data[i+3] === 0) {
-                            gameStatus = data[i];
-                            break;
-                        }
-                    }
-                    
-                    console.log(`Account ${index}: Successfully parsed game data`);
-                    console.log(`- Creator: ${creator.toString()}`);
-                    console.log(`- Max players: ${maxPlayers}`);
-                    console.log(`- Bet amount: ${betAmount.toString()}`);
-                    console.log(`- Players count: ${playersLen}`);
-                    console.log(`- Game status: ${gameStatus}`);
-                    
-                    return {
-                        id: account.pubkey.toString(),
-                        creator: creator.toString(),
-                        betAmount: betAmount.toString(),
-                        maxPlayers,
-                        currentPlayers: playersLen,
-                        deposited: depositedPlayersLen,
-                        status: gameStatus
-                    };
-                } catch (parseError) {
-                    console.error(`Account ${index}: Error parsing game structure: ${parseError.message}`);
-                    return null;
-                }
-            } catch (error) {
-                console.error(`Account ${index}: Error parsing account: ${error.message}`);
-                return null;
-            }
-        }));
+            });
+        }
         
-        // Filter out null values and games that are full or not waiting for players
+        // Filter out games that filled up or started since they were listed
         const availableGames = games.filter(game => 
-            game !== null && 
             game.currentPlayers < game.maxPlayers &&
             game.status === 0 // 0 = WaitingForPlayers in the GameStatus enum
         );
@@ -317,18 +430,40 @@ export async function joinGame(wallet, gameAccountPubkey) {
         if (!accountInfo) {
             throw new Error("Game account not found");
         }
+        const game = decodeGame(accountInfo.data);
+        if (!game.mint.equals(PublicKey.default)) {
+            throw new Error("Token tables are not supported by this app yet.");
+        }
         
-        // Create the join_game instruction
+        const [profilePubkey] = PublicKey.findProgramAddressSync(
+            [Buffer.from("profile"), wallet.publicKey.toBuffer()],
+            PROGRAM_ID
+        );
+        const lobbyPubkey = await findGameLobby(connection, gameAccount, game);
+        
+        // Head of the player's dice hash chain, revealed one link per roll
+        const { diceSecret, commitment } = newDiceChain();
+        
+        // Create the join_game instruction. Optional accounts left out are passed
+        // as the program id
         const joinGameIx = new TransactionInstruction({
             programId: PROGRAM_ID,
             keys: [
                 { pubkey: gameAccount, isSigner: false, isWritable: true },
                 { pubkey: wallet.publicKey, isSigner: true, isWritable: true },
+                { pubkey: profilePubkey, isSigner: false, isWritable: true },
+                { pubkey: lobbyPubkey || PROGRAM_ID, isSigner: false, isWritable: !!lobbyPubkey },
+                { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // mint
+                { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // vault
+                { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // player_token_account
+                { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // token_program
                 { pubkey: SystemProgram.programId, isSigner: false, isWritable: false }
             ],
             data: Buffer.from([
                 // Instruction discriminator for join_game from IDL
-                107, 112, 18, 38, 56, 173, 60, 128
+                107, 112, 18, 38, 56, 173, 60, 128,
+                // commitment ([u8; 32])
+                ...commitment
             ])
         });
         
@@ -357,7 +492,8 @@ export async function joinGame(wallet, gameAccountPubkey) {
         
         return {
             txid: signature,
-            gameAccountPubkey: gameAccount
+            gameAccountPubkey: gameAccount,
+            diceSecret
         };
     } catch (error) {
         console.error("Error joining game:", error);
@@ -372,10 +508,15 @@ export async function joinGame(wallet, gameAccountPubkey) {
 }
 
 /**
- * Roll the dice in the game
- * Simplified for React Native compatibility
+ * Roll the dice in the game, revealing the next link of the player's hash chain.
+ *
+ * The program requests each roll as the turn passes, so normally the reveal is
+ * sent straight away. A seat whose request lapsed or was never made asks for one
+ * first, and the reveal then goes out once that request has landed. The roll is
+ * left on the game for a following move_piece; it comes back as null when it
+ * could not be played and the turn has already passed.
  */
-export async function rollDice(wallet, gameAccountPubkey) {
+export async function rollDice(wallet, gameAccountPubkey, diceSecret) {
     if (!wallet || !wallet.publicKey) {
         throw new Error("Wallet is required to roll dice.");
     }
@@ -386,16 +527,70 @@ export async function rollDice(wallet, gameAccountPubkey) {
         const connection = getConnection();
         const gameAccount = new PublicKey(gameAccountPubkey);
         
+        const accountInfo = await connection.getAccountInfo(gameAccount);
+        if (!accountInfo) {
+            throw new Error("Game account not found");
+        }
+        const game = decodeGame(accountInfo.data);
+        if (!game.players[game.turn].equals(wallet.publicKey)) {
+            throw new Error("It is not your turn.");
+        }
+        
+        // Games created before history was kept have no move log
+        const [historyPubkey] = PublicKey.findProgramAddressSync(
+            [Buffer.from("history"), gameAccount.toBuffer()],
+            PROGRAM_ID
+        );
+        const history = game.history ? historyPubkey : PROGRAM_ID;
+        
+        if (!game.randomnessRequested) {
+            const requestRollIx = new TransactionInstruction({
+                programId: PROGRAM_ID,
+                keys: [
+                    { pubkey: gameAccount, isSigner: false, isWritable: true },
+                    { pubkey: wallet.publicKey, isSigner: true, isWritable: true },
+                    { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // session
+                    { pubkey: history, isSigner: false, isWritable: game.history },
+                    { pubkey: SystemProgram.programId, isSigner: false, isWritable: false }
+                ],
+                data: Buffer.from([
+                    // Instruction discriminator for request_roll
+                    98, 118, 98, 29, 96, 208, 255, 97
+                ])
+            });
+            const requestTransaction = new Transaction();
+            requestTransaction.add(requestRollIx);
+            requestTransaction.recentBlockhash = (await connection.getRecentBlockhash()).blockhash;
+            requestTransaction.feePayer = wallet.publicKey;
+            
+            // The reveal has to land in a later slot than the request
+            const requestTxid = await sendAndConfirmTransaction(
+                connection,
+                requestTransaction,
+                [wallet]
+            );
+            console.log("Roll requested. TxID:", requestTxid);
+        }
+        
+        const reveal = nextReveal(diceSecret, game.commitments[game.turn]);
+        
         // Create the request_randomness instruction
         const rollDiceIx = new TransactionInstruction({
             programId: PROGRAM_ID,
             keys: [
                 { pubkey: gameAccount, isSigner: false, isWritable: true },
-                { pubkey: wallet.publicKey, isSigner: true, isWritable: true }
+                { pubkey: wallet.publicKey, isSigner: true, isWritable: true },
+                { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // session
+                { pubkey: history, isSigner: false, isWritable: game.history },
+                { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // profile
+                { pubkey: SYSVAR_SLOT_HASHES_PUBKEY, isSigner: false, isWritable: false },
+                { pubkey: SystemProgram.programId, isSigner: false, isWritable: false }
             ],
-            data: new Uint8Array([
+            data: Buffer.from([
                 // Instruction discriminator for request_randomness
-                213, 5, 173, 166, 37, 236, 31, 18
+                213, 5, 173, 166, 37, 236, 31, 18,
+                // reveal ([u8; 32])
+                ...reveal
             ])
         });
         
@@ -416,16 +611,14 @@ export async function rollDice(wallet, gameAccountPubkey) {
         console.log("Transaction sent successfully. TxID:", txid);
         
         // Fetch the updated game data to get the dice roll result
-        const account = await connection.getAccountInfo(gameAccount);
+        const rolled = decodeGame((await connection.getAccountInfo(gameAccount)).data);
         
-        // For now, return a simple result
-        // In a real implementation, we would parse the account data to get the actual dice roll
         return {
             txid,
-            diceRoll: 6, // placeholder, should be extracted from account data
+            diceRoll: rolled.diceRoll,
             gameData: {
-                turn: 1, // placeholder, should be extracted from account data
-                players: [wallet.publicKey]
+                turn: rolled.turn,
+                players: rolled.players.slice(0, rolled.maxPlayers)
             }
         };
     } catch (error) {
@@ -628,123 +821,17 @@ export async function fetchJoinedGames(wallet) {
     const connection = getConnection();
     
     try {
-        // Get all program accounts
-        const accounts = await connection.getProgramAccounts(PROGRAM_ID);
-        console.log(`Found ${accounts.length} program accounts`);
-        
-        if (accounts.length === 0) {
-            console.log("No program accounts found");
-            return [];
-        }
-        
-        // Parse each account to get game details
-        const games = await Promise.all(accounts.map(async (account, index) => {
-            try {
-                if (!account || !account.account || !account.account.data) {
-                    console.log(`Account ${index}: Invalid account data structure`);
-                    return null;
-                }
-
-                // Convert account data to Uint8Array if it's not already
-                const data = new Uint8Array(account.account.data);
-                
-                // Check if we have enough data for the discriminator
-                if (data.length < 8) {
-                    console.log(`Account ${index}: Data too short for discriminator`);
-                    return null;
-                }
-                
-                // The account data starts with an 8-byte discriminator
-                const discriminator = data.slice(0, 8);
-                
-                // Try both discriminators since there might be a version mismatch
-                const gameStateDiscriminator1 = new Uint8Array([103, 195, 198, 221, 41, 211, 145, 83]);
-                const gameStateDiscriminator2 = new Uint8Array([144, 94, 208, 172, 248, 99, 134, 120]);
-                
-                // Check against both possible discriminators
-                const matches1 = discriminator.every((byte, i) => byte === gameStateDiscriminator1[i]);
-                const matches2 = discriminator.every((byte, i) => byte === gameStateDiscriminator2[i]);
-                
-                if (!matches1 && !matches2) {
-                    return null;
-                }
-                
-                // Try to parse game state based on the Anchor layout
-                try {
-                    // Skip discriminator (8 bytes)
-                    let offset = 8;
-                    
-                    // Read creator (Pubkey) - 32 bytes
-                    const creatorBytes = data.slice(offset, offset + 32);
-                    const creator = new PublicKey(creatorBytes);
-                    offset += 32;
-                    
-                    // Read players array length (u32) - 4 bytes
-                    const playersLenBytes = data.slice(offset, offset + 4);
-                    const playersLen = new Uint32Array(playersLenBytes.buffer)[0];
-                    offset += 4;
-                    
-                    // Read players array
-                    const players = [];
-                    for (let i = 0; i < playersLen; i++) {
-                        const playerBytes = data.slice(offset, offset + 32);
-                        players.push(new PublicKey(playerBytes).toString());
-                        offset += 32;
-                    }
-                    
-                    // Check if current wallet is in the players array
-                    const isJoined = players.includes(wallet.publicKey.toString());
-                    
-                    if (!isJoined) {
-                        return null;
-                    }
-                    
-                    // Skip deposited_players array
-                    const depositedPlayersLenBytes = data.slice(offset, offset + 4);
-                    const depositedPlayersLen = new Uint32Array(depositedPlayersLenBytes.buffer)[0];
-                    offset += 4;
-                    offset += depositedPlayersLen * 32;
-                    
-                    // Read max_players (u8)
-                    const maxPlayers = data[offset];
-                    offset += 1;
-                    
-                    // Read bet_amount (u64)
-                    const betAmountBytes = data.slice(offset, offset + 8);
-                    const betAmount = new BigUint64Array(betAmountBytes.buffer)[0];
-                    
-                    // Get the game status
-                    const gameStatusOffset = data.length - 100; // Rough estimate
-                    let gameStatus = 0;
-                    
-                    for (let i = gameStatusOffset; i < data.length - 10; i++) {
-                        if (data[i] <= 4 && data[i+1] === 0 && data[i+2] === 0 && data[i+3] === 0) {
-                            gameStatus = data[i];
-                            break;
-                        }
-                    }
-                    
-                    return {
-                        id: account.pubkey.toString(),
-                        creator: creator.toString(),
-                        betAmount: betAmount.toString(),
-                        maxPlayers,
-                        currentPlayers: playersLen,
-                        status: gameStatus,
-                        isCreator: creator.toString() === wallet.publicKey.toString()
-                    };
-                } catch (parseError) {
-                    console.error(`Account ${index}: Error parsing game structure: ${parseError.message}`);
-                    return null;
-                }
-            } catch (error) {
-                console.error(`Account ${index}: Error parsing account: ${error.message}`);
-                return null;
-            }
-        }));
-        
-        // Filter out null values and return only joined games
-        const joinedGames = games.filter(game => game !== null);
+        const accounts = await fetchAccountsOfType(connection, GAME_ACCOUNT_SIZE, GAME_STATE_DISCRIMINATOR);
+        console.log(`Found ${accounts.length} game accounts`);
+        
+        // Keep the games the wallet holds a seat in
+        const joinedGames = accounts
+            .map(({ pubkey, account }) => ({ pubkey, game: decodeGame(account.data) }))
+            .filter(({ game }) => game.players.some(player => player.equals(wallet.publicKey)))
+            .map(({ pubkey, game }) => ({
+                ...gameSummary(pubkey, game),
+                isCreator: game.creator.equals(wallet.publicKey)
+            }));
         
         console.log(`Found ${joinedGames.length} joined games`);
         
@@ -754,12 +841,3 @@ export async function fetchJoinedGames(wallet) {
         throw error;
     }
 }
-
-
-
-
-
-
-
-
-